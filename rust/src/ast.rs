@@ -1,14 +1,181 @@
+use crate::{Definition, Interner, RuleSet, Symbol};
 use im::Vector;
-use lasso::Spur;
-use lasso::ThreadedRodeo;
+use lasso::{Key, Spur, ThreadedRodeo};
+use std::cmp::Ordering;
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
 use std::fmt;
-use typed_arena::Arena;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::ops::Deref;
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, Weak};
 
-/// The mlatu engine
+/// The mlatu engine, which keeps its words in an interner of type `I`.
+///
+/// An engine can intern up to 2^24 words, about 16 million, not counting those of its ancestors.
 #[derive(Default)]
-pub struct Engine {
-    arena: Arena<Term>,
-    rodeo: ThreadedRodeo,
+pub struct Engine<I = ThreadedRodeo> {
+    rodeo: I,
+    quotes: Arc<Quotes>,
+    symbols: Mutex<HashMap<Spur, Symbol>>,
+    /// The words generated by the engine itself
+    generated: ThreadedRodeo,
+    /// The number of fresh words made by the engine and its relatives
+    fresh: Arc<AtomicUsize>,
+    parent: Option<Arc<Self>>,
+    /// The number of ancestors of the engine
+    depth: usize,
+}
+
+// Engines are shared between threads that rewrite in parallel
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Engine>();
+};
+
+/// How many bits of a spur are the index of its word in the interner of the engine that interned
+/// it, or among the words the engine generated. This limits every engine to 2^24 words of its own.
+const INDEX_BITS: usize = 24;
+
+/// The bit of a spur that tells whether its word was generated
+const GENERATED: usize = 1 << INDEX_BITS;
+
+/// Where the depth of the engine that interned or generated a word starts in its spur
+const DEPTH_SHIFT: usize = INDEX_BITS + 1;
+
+/// The most ancestors an engine can have, so that every spur still fits in the bits above
+/// `DEPTH_SHIFT`
+const MAX_DEPTH: usize = 126;
+
+/// Whether a word was made by `Engine::generate` or `Engine::fresh_word` rather than interned
+#[must_use]
+pub fn is_generated(spur: Spur) -> bool {
+    spur.into_usize() & GENERATED != 0
+}
+
+/// The name of a word, kept either by the interner of an engine or with its generated words
+pub enum Name<'a, I: Interner + 'a> {
+    Interned(I::Word<'a>),
+    Generated(&'a str),
+}
+
+impl<I: Interner> Deref for Name<'_, I> {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        match self {
+            Self::Interned(word) => word,
+            Self::Generated(word) => word,
+        }
+    }
+}
+
+impl<I: Interner> fmt::Display for Name<'_, I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self)
+    }
+}
+
+/// The quotes an engine has made that are still in use, split by the hash of their contents into
+/// shards that are locked on their own, so that threads making quotes rarely wait for each other
+struct Quotes {
+    shards: Box<[Mutex<Shard>]>,
+    hasher: RandomState,
+    next_id: AtomicUsize,
+}
+
+/// Some of the quotes an engine has made that are still in use, by the hash of their contents
+#[derive(Default)]
+struct Shard {
+    table: HashMap<u64, Vec<Weak<Node>>>,
+    /// The number of entries in the table, dropped quotes included
+    entries: usize,
+    /// How many entries there can be before the dropped quotes are swept out of the table
+    threshold: usize,
+}
+
+impl Default for Quotes {
+    fn default() -> Self {
+        Self {
+            shards: (0..Self::SHARDS).map(|_| Mutex::default()).collect(),
+            hasher: RandomState::new(),
+            next_id: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl Quotes {
+    const SHARDS: usize = 16;
+
+    fn shard(&self, hash: u64) -> MutexGuard<'_, Shard> {
+        let index = usize::try_from(hash % Self::SHARDS as u64).unwrap_or(0);
+        self.shards[index]
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Finds the node of a quote with the given contents, making one if there is none
+    fn get_or_insert(&self, terms: Vector<Term>) -> Arc<Node> {
+        let hash = self.hasher.hash_one(&terms);
+        self.shard(hash).get_or_insert(hash, terms, || {
+            self.next_id.fetch_add(1, AtomicOrdering::Relaxed)
+        })
+    }
+
+    /// Removes the entries of the quotes that are no longer in use, giving back how many there were
+    fn sweep(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| shard.lock().unwrap_or_else(PoisonError::into_inner).sweep())
+            .sum()
+    }
+}
+
+impl Shard {
+    /// The fewest entries there can be before the dropped quotes are swept out of the table
+    const MIN_THRESHOLD: usize = 64;
+
+    fn get_or_insert(
+        &mut self,
+        hash: u64,
+        terms: Vector<Term>,
+        id: impl FnOnce() -> usize,
+    ) -> Arc<Node> {
+        let found = self
+            .table
+            .get(&hash)
+            .into_iter()
+            .flatten()
+            .find_map(|node| node.upgrade().filter(|node| node.terms == terms));
+        if let Some(node) = found {
+            return node;
+        }
+        if self.entries >= self.threshold {
+            let _ = self.sweep();
+        }
+        self.entries += 1;
+        let node = Arc::new(Node {
+            id: id(),
+            hash,
+            terms,
+        });
+        self.table
+            .entry(hash)
+            .or_default()
+            .push(Arc::downgrade(&node));
+        node
+    }
+
+    fn sweep(&mut self) -> usize {
+        self.table.retain(|_, nodes| {
+            nodes.retain(|node| node.strong_count() > 0);
+            !nodes.is_empty()
+        });
+        let removed = self.entries;
+        self.entries = self.table.values().map(Vec::len).sum();
+        self.threshold = (self.entries * 2).max(Self::MIN_THRESHOLD);
+        removed - self.entries
+    }
 }
 
 impl Engine {
@@ -16,22 +183,363 @@ impl Engine {
     pub fn new() -> Self {
         Self::default()
     }
+}
+
+impl<I: Interner> Engine<I> {
+    /// Makes an engine that keeps its words in the given interner
+    #[must_use]
+    pub fn with_interner(interner: I) -> Self {
+        Self {
+            rodeo: interner,
+            quotes: Arc::default(),
+            symbols: Mutex::default(),
+            generated: ThreadedRodeo::default(),
+            fresh: Arc::default(),
+            parent: None,
+            depth: 0,
+        }
+    }
 
-    #[allow(clippy::mut_from_ref)]
-    pub fn alloc(&self, term: Term) -> &mut Term {
-        self.arena.alloc(term)
+    /// Makes an engine that knows all the words and quotes of this one, but keeps the words it
+    /// interns itself to itself, so that they are freed when it is dropped.
+    ///
+    /// A child looks up words in its own interner before those of its ancestors, so a word keeps
+    /// the spur it first got in the child. A word this engine interns after a child interned it
+    /// still gets a different spur in each of them, so terms made by this engine from then on do
+    /// not match the terms of the child. Children are best made from an engine that is done
+    /// interning words, like a prelude.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the engine already has 126 ancestors
+    #[must_use]
+    pub fn child(self: &Arc<Self>) -> Self
+    where
+        I: Default,
+    {
+        assert!(
+            self.depth < MAX_DEPTH,
+            "engines can not be nested that deeply"
+        );
+        Self {
+            rodeo: I::default(),
+            quotes: Arc::clone(&self.quotes),
+            symbols: Mutex::default(),
+            generated: ThreadedRodeo::default(),
+            fresh: Arc::clone(&self.fresh),
+            parent: Some(Arc::clone(self)),
+            depth: self.depth + 1,
+        }
+    }
+
+    /// Turns the engine into one that can not intern new words, but keeps its quotes
+    ///
+    /// # Panics
+    ///
+    /// Panics if the engine has a parent
+    #[must_use]
+    pub fn freeze(self) -> Engine<I::Frozen> {
+        assert!(
+            self.parent.is_none(),
+            "only engines without a parent can be frozen"
+        );
+        Engine {
+            rodeo: self.rodeo.freeze(),
+            quotes: self.quotes,
+            symbols: self.symbols,
+            generated: self.generated,
+            fresh: self.fresh,
+            parent: None,
+            depth: 0,
+        }
     }
 
+    /// Interns a word, unless this engine or one of its ancestors already did
+    ///
+    /// # Panics
+    ///
+    /// Panics if the interner can not take the word, because it is frozen or full. Each engine can
+    /// intern up to 2^24 words, about 16 million, since the bits of a spur above those tell which
+    /// engine interned it.
     pub fn get_or_intern(&self, s: String) -> Spur {
-        self.rodeo.get_or_intern(s)
+        self.try_get_or_intern(s)
+            .expect("the interner should be able to take the word")
+    }
+
+    /// Interns a word, unless this engine or one of its ancestors already did, giving back `None`
+    /// if the interner can not take it, or if the engine already interned 2^24 words
+    pub fn try_get_or_intern(&self, s: String) -> Option<Spur> {
+        self.get(&s)
+            .or_else(|| self.rodeo.get_or_intern(s).and_then(|spur| self.own(spur)))
+    }
+
+    /// Makes a new word that starts with the given prefix, which is different from every word
+    /// that was or will be interned otherwise. The prefix is followed by a `.` and a number, and
+    /// since a `.` ends a word when parsing, no parsed word can be the same. The word is made with
+    /// `Engine::generate`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the prefix is not read as a single word, because it is empty, has whitespace,
+    /// parentheses, `=`, `.` or primitives in it, or starts with `$` like a variable, since the
+    /// word would then not be printed unambiguously. Also panics if the engine already generated
+    /// 2^24 words.
+    pub fn fresh_word(&self, prefix: &str) -> Spur {
+        assert!(
+            crate::parse::is_word(prefix),
+            "the prefix of a fresh word should be read as a single word"
+        );
+        loop {
+            let n = self.fresh.fetch_add(1, AtomicOrdering::Relaxed);
+            let word = format!("{prefix}.{n}");
+            if self.get(&word).is_none() && self.generated(&word).is_none() {
+                return self.generate(&word);
+            }
+        }
+    }
+
+    /// Gives back a generated word with the given name, which is the same for this engine and its
+    /// descendants every time, but different from every interned word, even one with the same
+    /// name. Generated words are kept apart from the interner, so even a frozen engine can make
+    /// them, and the words a child generates are freed along with it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the engine already generated 2^24 words
+    pub fn generate(&self, name: &str) -> Spur {
+        self.generated(name).unwrap_or_else(|| {
+            let index = self.generated.get_or_intern(name).into_usize();
+            assert!(index < 1 << INDEX_BITS, "too many words were generated");
+            Spur::try_from_usize(self.depth << DEPTH_SHIFT | GENERATED | index)
+                .expect("a generated spur fits")
+        })
+    }
+
+    /// Finds the spur of a word that this engine or one of its ancestors generated, looking in this
+    /// engine first
+    fn generated(&self, name: &str) -> Option<Spur> {
+        self.generated
+            .get(name)
+            .and_then(|spur| {
+                Spur::try_from_usize(self.depth << DEPTH_SHIFT | GENERATED | spur.into_usize())
+            })
+            .or_else(|| {
+                self.parent
+                    .as_ref()
+                    .and_then(|parent| parent.generated(name))
+            })
+    }
+
+    /// Finds the spur of a word that this engine or one of its ancestors interned, looking in this
+    /// engine first, so that the word has the same spur every time
+    #[must_use]
+    pub fn get(&self, s: &str) -> Option<Spur> {
+        self.rodeo
+            .get(s)
+            .and_then(|spur| self.own(spur))
+            .or_else(|| self.parent.as_ref().and_then(|parent| parent.get(s)))
+    }
+
+    /// Turns a spur of the interner of this engine into one that tells which engine interned it,
+    /// giving back `None` if the engine has interned too many words for that
+    fn own(&self, spur: Spur) -> Option<Spur> {
+        let index = spur.into_usize();
+        (index < 1 << INDEX_BITS)
+            .then(|| Spur::try_from_usize(self.depth << DEPTH_SHIFT | index))
+            .flatten()
+    }
+
+    /// Finds the word of a spur that this engine or one of its ancestors interned or generated.
+    ///
+    /// A spur only tells the depth of the engine that made it, so a spur of an engine that is
+    /// not an ancestor, but no deeper than this one, like a sibling or an unrelated engine, can not
+    /// be told apart from the spurs of the ancestor at that depth. It gives back the word of that
+    /// ancestor with the same index, or panics if there is none.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the spur was made by an engine deeper than this one, like a child
+    pub fn resolve(&self, s: &Spur) -> Name<'_, I> {
+        let key = s.into_usize();
+        let index = Spur::try_from_usize(key & ((1 << INDEX_BITS) - 1)).expect("an index fits");
+        assert!(
+            key >> DEPTH_SHIFT <= self.depth,
+            "the spur was made by an engine deeper than this one"
+        );
+        let mut engine = self;
+        while engine.depth > key >> DEPTH_SHIFT {
+            engine = engine
+                .parent
+                .as_deref()
+                .expect("every engine below the root has a parent");
+        }
+        if is_generated(*s) {
+            Name::Generated(engine.generated.resolve(&index))
+        } else {
+            Name::Interned(engine.rodeo.resolve(&index))
+        }
+    }
+
+    /// Finds what this engine or one of its ancestors knows about a word
+    #[must_use]
+    pub fn symbol(&self, word: Spur) -> Option<Symbol> {
+        let symbols = self.symbols.lock().unwrap_or_else(PoisonError::into_inner);
+        symbols.get(&word).cloned().or_else(|| {
+            drop(symbols);
+            self.parent.as_ref().and_then(|parent| parent.symbol(word))
+        })
+    }
+
+    /// Changes what this engine knows about a word, starting from what its ancestors know
+    pub fn update_symbol(&self, word: Spur, f: impl FnOnce(&mut Symbol)) {
+        let inherited = self.parent.as_ref().and_then(|parent| parent.symbol(word));
+        let mut symbols = self.symbols.lock().unwrap_or_else(PoisonError::into_inner);
+        f(symbols
+            .entry(word)
+            .or_insert_with(|| inherited.unwrap_or_default()));
     }
 
-    pub fn resolve(&self, s: &Spur) -> &str {
-        self.rodeo.resolve(s)
+    /// Records the rules of a set that were parsed from a source as the definitions of the words
+    /// they define, replacing the definitions recorded for the same source before. The source can
+    /// be any id the caller picks, like the index of a file. Rules without a location, like those
+    /// inserted into the set after parsing, are not recorded.
+    pub fn define(&self, source: usize, rules: &RuleSet) {
+        for (word, symbol) in self.symbols() {
+            if symbol.definitions.iter().any(|d| d.source == source) {
+                self.update_symbol(word, |symbol| {
+                    symbol.definitions.retain(|d| d.source != source);
+                });
+            }
+        }
+        for (id, rule) in rules.iter() {
+            let head = rules
+                .location(id)
+                .and_then(|location| Some((location, location.head?)));
+            if let (Some(word), Some((location, head))) = (rule.head(), head) {
+                self.update_symbol(word, |symbol| {
+                    symbol.definitions.push(Definition {
+                        source,
+                        rule: id,
+                        span: location.span,
+                        head,
+                    });
+                });
+            }
+        }
+    }
+
+    /// Gives back everything this engine and its ancestors know about words, ordered by spur
+    #[must_use]
+    pub fn symbols(&self) -> Vec<(Spur, Symbol)> {
+        let mut symbols: HashMap<_, _> = self
+            .parent
+            .as_ref()
+            .map(|parent| parent.symbols().into_iter().collect())
+            .unwrap_or_default();
+        symbols.extend(
+            self.symbols
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .iter()
+                .map(|(word, symbol)| (*word, symbol.clone())),
+        );
+        let mut symbols: Vec<_> = symbols.into_iter().collect();
+        symbols.sort_unstable_by_key(|(word, _)| *word);
+        symbols
+    }
+
+    /// Gives back the quote of the given terms, which is the same quote every time the engine is
+    /// given the same terms while the quote is still in use
+    pub fn quote(&self, terms: Vector<Term>) -> Quote {
+        Quote(self.quotes.get_or_insert(terms))
+    }
+
+    /// Frees what is left of the quotes that are no longer in use right away, instead of waiting
+    /// for enough of them to pile up, giving back how many there were. The quotes are shared with
+    /// the parent and the children of the engine, so theirs are freed too.
+    pub fn collect(&self) -> usize {
+        self.quotes.sweep()
     }
 }
 
-#[derive(Clone, PartialEq, PartialOrd, Eq, Ord, Hash, Debug)]
+/// A hash-consed quote. All quotes with the same contents made by one engine, its ancestors and
+/// their children share a node with a unique id, so comparing and hashing them takes constant time.
+#[derive(Clone)]
+pub struct Quote(Arc<Node>);
+
+struct Node {
+    id: usize,
+    /// The hash of the contents, which stays the same when the quote is dropped and made again
+    hash: u64,
+    terms: Vector<Term>,
+}
+
+impl Quote {
+    /// The id of the quote, unique among the quotes of the engine that made it
+    #[must_use]
+    pub fn id(&self) -> usize {
+        self.0.id
+    }
+
+    /// The contents of the quote
+    #[must_use]
+    pub fn terms(&self) -> &Vector<Term> {
+        &self.0.terms
+    }
+}
+
+impl Deref for Quote {
+    type Target = Vector<Term>;
+
+    fn deref(&self) -> &Vector<Term> {
+        self.terms()
+    }
+}
+
+impl PartialEq for Quote {
+    /// Compares whether the quotes are the same node, which quotes of the same engine with the same
+    /// contents are, while quotes of different engines never are
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Quote {}
+
+impl Hash for Quote {
+    /// Hashes the contents rather than the id, so that a quote hashes the same after it was
+    /// dropped and made again with a new id
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash.hash(state);
+    }
+}
+
+impl PartialOrd for Quote {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Quote {
+    /// Orders quotes by their contents, so that the order does not depend on when they were made
+    fn cmp(&self, other: &Self) -> Ordering {
+        if self == other {
+            return Ordering::Equal;
+        }
+        self.terms()
+            .cmp(other.terms())
+            .then_with(|| self.id().cmp(&other.id()))
+            .then_with(|| Arc::as_ptr(&self.0).cmp(&Arc::as_ptr(&other.0)))
+    }
+}
+
+impl fmt::Debug for Quote {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.terms().fmt(f)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, PartialOrd, Eq, Ord, Hash, Debug)]
 pub enum Primitive {
     Copy,
     Discard,
@@ -59,26 +567,41 @@ impl fmt::Display for Primitive {
 pub enum Term {
     Word(Spur),
     Prim(Primitive),
-    Quote(Vector<Self>),
+    Quote(Quote),
+    /// A variable in a rule that stands for any quote
+    Var(Spur),
+    /// A variable in a rule that stands for the contents of a quote
+    SeqVar(Spur),
 }
 
-#[allow(clippy::mut_from_ref)]
 impl Term {
     /// Makes a word term out of a spur
     #[must_use]
-    pub fn make_word(engine: &Engine, spur: Spur) -> &mut Self {
-        engine.alloc(Self::Word(spur))
+    pub const fn make_word<I>(_engine: &Engine<I>, spur: Spur) -> Self {
+        Self::Word(spur)
     }
 
     /// Makes a quote out of anything that can be turned into a sequence of terms
     #[must_use]
-    pub fn make_quote(engine: &Engine, terms: Vector<Self>) -> &mut Self {
-        engine.alloc(Self::Quote(terms))
+    pub fn make_quote<I: Interner>(engine: &Engine<I>, terms: Vector<Self>) -> Self {
+        Self::Quote(engine.quote(terms))
+    }
+
+    #[must_use]
+    pub const fn make_prim<I>(_engine: &Engine<I>, primitive: Primitive) -> Self {
+        Self::Prim(primitive)
     }
 
+    /// Makes a variable that stands for any quote out of a spur
     #[must_use]
-    pub fn make_prim(engine: &Engine, primitive: Primitive) -> &mut Self {
-        engine.alloc(Self::Prim(primitive))
+    pub const fn make_var<I>(_engine: &Engine<I>, spur: Spur) -> Self {
+        Self::Var(spur)
+    }
+
+    /// Makes a variable that stands for the contents of a quote out of a spur
+    #[must_use]
+    pub const fn make_seq_var<I>(_engine: &Engine<I>, spur: Spur) -> Self {
+        Self::SeqVar(spur)
     }
 
     #[must_use]
@@ -99,4 +622,174 @@ impl Rule {
     pub fn new() -> Self {
         Self::default()
     }
+
+    /// The word the rule defines, which is the rightmost word of its redex
+    #[must_use]
+    pub fn head(&self) -> Option<Spur> {
+        self.redex.iter().rev().find_map(|term| match term {
+            Term::Word(spur) => Some(*spur),
+            _ => None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+    use std::sync::Arc;
+
+    #[test]
+    fn quote_sharing_test() {
+        let engine = Engine::new();
+        let terms = parse::terms(&engine, "((a) b) ((a) b) (a)").unwrap();
+        let (Term::Quote(first), Term::Quote(second), Term::Quote(third)) =
+            (&terms[0], &terms[1], &terms[2])
+        else {
+            panic!("expected three quotes");
+        };
+        assert!(Arc::ptr_eq(&first.0, &second.0));
+        assert_eq!(first, second);
+        assert_ne!(first, third);
+        assert_eq!(first[0], Term::Quote(third.clone()));
+        assert!(third < first);
+
+        // Every engine counts the ids of its quotes from zero
+        let (one, two) = (Engine::new(), Engine::new());
+        let a = parse::term(&one, "(a)").unwrap();
+        let b = parse::term(&two, "(b c d)").unwrap();
+        assert_ne!(a, b);
+        assert_ne!(parse::term(&two, "(a)").unwrap(), a);
+    }
+
+    #[test]
+    fn dropped_quotes_test() {
+        let engine = Engine::new();
+        let a = Term::make_word(&engine, engine.get_or_intern("a".to_owned()));
+        let kept = engine.quote(im::vector![a.clone()]);
+        for n in 0..10_000 {
+            let word = engine.get_or_intern(format!("w{n}"));
+            let _ = engine.quote(im::vector![Term::make_word(&engine, word)]);
+        }
+        let entries: usize = (engine.quotes.shards.iter())
+            .map(|shard| shard.lock().unwrap().entries)
+            .sum();
+        assert!(entries < Quotes::SHARDS * 2 * Shard::MIN_THRESHOLD);
+        assert_eq!(engine.quote(im::vector![a]), kept);
+    }
+
+    #[test]
+    fn shards_test() {
+        let engine = Engine::new();
+        let quote = |name: &str| {
+            let word = Term::make_word(&engine, engine.get_or_intern(name.to_owned()));
+            im::vector![word]
+        };
+        let locked = quote("a");
+        let shard =
+            |terms: &Vector<Term>| engine.quotes.hasher.hash_one(terms) % Quotes::SHARDS as u64;
+        let other = (0..1000)
+            .map(|n| quote(&format!("b{n}")))
+            .find(|terms| shard(terms) != shard(&locked))
+            .unwrap();
+        // Holding the lock of one shard does not keep other threads from making quotes in another
+        let _guard = engine.quotes.shard(engine.quotes.hasher.hash_one(&locked));
+        let made = std::thread::scope(|scope| scope.spawn(|| engine.quote(other.clone())).join());
+        assert_eq!(made.unwrap().terms(), &other);
+    }
+
+    #[test]
+    fn child_test() {
+        let parent = Arc::new(Engine::new());
+        let a = parent.get_or_intern("a".to_owned());
+        let quote = parent.quote(im::vector![Term::Word(a)]);
+        let child = parent.child();
+        assert_eq!(child.get_or_intern("a".to_owned()), a);
+        let b = child.get_or_intern("b".to_owned());
+        assert_ne!(a, b);
+        assert_eq!(&*child.resolve(&a), "a");
+        assert_eq!(&*child.resolve(&b), "b");
+        assert_eq!(parent.get("b"), None);
+        assert_eq!(child.quote(im::vector![Term::Word(a)]), quote);
+
+        let grandchild = Arc::new(child).child();
+        assert_eq!(grandchild.get("b"), Some(b));
+        let c = grandchild.get_or_intern("c".to_owned());
+        assert_eq!(&*grandchild.resolve(&c), "c");
+        drop(grandchild);
+        assert_eq!(parent.get("c"), None);
+
+        // A word keeps its spur in a child after the parent interns it too
+        let parent = Arc::new(Engine::new());
+        let child = parent.child();
+        let before = child.get_or_intern("foo".to_owned());
+        let interned = parent.get_or_intern("foo".to_owned());
+        assert_ne!(before, interned);
+        assert_eq!(child.get_or_intern("foo".to_owned()), before);
+        assert_eq!(child.get("foo"), Some(before));
+    }
+
+    #[test]
+    fn collect_test() {
+        let engine = Engine::new();
+        let a = Term::make_word(&engine, engine.get_or_intern("a".to_owned()));
+        let kept = engine.quote(im::vector![a.clone()]);
+        let dropped = engine.quote(im::vector![a.clone(), a]);
+        drop(dropped);
+        assert_eq!(engine.collect(), 1);
+        assert_eq!(engine.collect(), 0);
+        assert_eq!(kept.len(), 1);
+    }
+
+    #[test]
+    fn fresh_word_test() {
+        let parent = Arc::new(Engine::new());
+        parent.get_or_intern("x.1".to_owned());
+        let first = parent.fresh_word("x");
+        let child = parent.child();
+        let second = child.fresh_word("x");
+        let third = parent.fresh_word("x");
+        assert_eq!(&*parent.resolve(&first), "x.0");
+        assert_eq!(&*child.resolve(&second), "x.2");
+        assert_eq!(&*parent.resolve(&third), "x.3");
+
+        let terms = im::vector![Term::make_word(&child, second)];
+        let printed = crate::pretty::terms(&child, terms);
+        assert_eq!(printed, "x.2");
+        assert!(crate::parse::terms(&child, &printed).is_err());
+
+        // The words a child generates are its own, and are freed along with it
+        assert_eq!(child.generate("x.0"), first);
+        assert_eq!(parent.generated("x.2"), None);
+        assert_eq!(child.generated("x.2"), Some(second));
+        drop(child);
+        assert_eq!(Arc::strong_count(&parent), 1);
+    }
+
+    #[test]
+    fn fresh_word_prefix_test() {
+        for prefix in ["", "a b", "(", "a=b", "x.y", "+", "$A", "é\t"] {
+            assert!(
+                !crate::parse::is_word(prefix),
+                "{prefix:?} should be rejected"
+            );
+        }
+        let engine = Engine::new();
+        assert_eq!(&*engine.resolve(&engine.fresh_word("a$")), "a$.0");
+    }
+
+    #[test]
+    #[should_panic(expected = "the spur was made by an engine deeper than this one")]
+    fn resolve_child_test() {
+        let parent = Arc::new(Engine::new());
+        parent.get_or_intern("a".to_owned());
+        let b = parent.child().get_or_intern("b".to_owned());
+        let _ = parent.resolve(&b);
+    }
+
+    #[test]
+    #[should_panic(expected = "the prefix of a fresh word should be read as a single word")]
+    fn fresh_word_space_test() {
+        let _ = Engine::new().fresh_word("a b");
+    }
 }
@@ -0,0 +1,238 @@
+use crate::confluence::{self, CriticalPair};
+use crate::{pattern, rewrite_with, Config, Engine, Interner, Rule, RuleSet, Term};
+use im::Vector;
+use lasso::{Spur, ThreadedRodeo};
+use std::cmp::Ordering;
+
+/// A well-founded order on sequences of terms, used to decide which way an equation becomes a rule
+pub trait Order<I: Interner = ThreadedRodeo> {
+    /// Compares two sequences of terms, or gives `None` if they can not be compared
+    fn compare(
+        &self,
+        engine: &Engine<I>,
+        left: &Vector<Term>,
+        right: &Vector<Term>,
+    ) -> Option<Ordering>;
+}
+
+/// Orders sequences of terms by their size, and then lexicographically.
+///
+/// Words that are in the precedence come before words that are not, in the order they are listed,
+/// and other words are ordered by their names. A sequence with variables is only greater than
+/// another if it has at least as many of each variable, so that every instance stays ordered.
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Default)]
+pub struct LengthLexicographic {
+    pub precedence: Vec<Spur>,
+}
+
+impl LengthLexicographic {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn compare_terms<I: Interner>(
+        &self,
+        engine: &Engine<I>,
+        left: &Term,
+        right: &Term,
+    ) -> Option<Ordering> {
+        let rank = |term: &Term| match term {
+            Term::Prim(_) => 0,
+            Term::Word(_) => 1,
+            Term::Quote(_) => 2,
+            Term::Var(_) | Term::SeqVar(_) => 3,
+        };
+        match (left, right) {
+            (Term::Var(_) | Term::SeqVar(_), _) | (_, Term::Var(_) | Term::SeqVar(_)) => {
+                (left == right).then_some(Ordering::Equal)
+            }
+            (Term::Prim(a), Term::Prim(b)) => Some(a.cmp(b)),
+            (Term::Word(a), Term::Word(b)) => {
+                let position = |word| self.precedence.iter().position(|p| p == word);
+                Some(match (position(a), position(b)) {
+                    (Some(a), Some(b)) => a.cmp(&b),
+                    (Some(_), None) => Ordering::Less,
+                    (None, Some(_)) => Ordering::Greater,
+                    (None, None) => (*engine.resolve(a)).cmp(&*engine.resolve(b)),
+                })
+            }
+            (Term::Quote(a), Term::Quote(b)) => self.compare(engine, a, b),
+            _ => Some(rank(left).cmp(&rank(right))),
+        }
+    }
+}
+
+/// The least size of any instance of the terms, where a sequence variable can stand for no terms
+fn size(terms: &Vector<Term>) -> usize {
+    terms
+        .iter()
+        .map(|term| match term {
+            Term::Quote(inner) => 1 + size(inner),
+            Term::SeqVar(_) => 0,
+            _ => 1,
+        })
+        .sum()
+}
+
+impl<I: Interner> Order<I> for LengthLexicographic {
+    fn compare(
+        &self,
+        engine: &Engine<I>,
+        left: &Vector<Term>,
+        right: &Vector<Term>,
+    ) -> Option<Ordering> {
+        let vars = pattern::variables(left).union(pattern::variables(right));
+        let dominates = |a: &Vector<Term>, b: &Vector<Term>| {
+            vars.iter()
+                .all(|var| pattern::occurrences(a, *var) >= pattern::occurrences(b, *var))
+        };
+        let lexicographic = || {
+            for (a, b) in left.iter().zip(right) {
+                match self.compare_terms(engine, a, b)? {
+                    Ordering::Equal => {}
+                    ordering => return Some(ordering),
+                }
+            }
+            // Sequence variables can stand for no terms, so only the other terms surely count
+            let length = |terms: &Vector<Term>| {
+                terms
+                    .iter()
+                    .filter(|term| !matches!(term, Term::SeqVar(_)))
+                    .count()
+            };
+            Some(length(left).cmp(&length(right)))
+        };
+        let ordering = match size(left).cmp(&size(right)) {
+            Ordering::Equal => lexicographic()?,
+            ordering => ordering,
+        };
+        match ordering {
+            Ordering::Greater if dominates(left, right) => Some(Ordering::Greater),
+            Ordering::Less if dominates(right, left) => Some(Ordering::Less),
+            Ordering::Equal if left == right => Some(Ordering::Equal),
+            _ => None,
+        }
+    }
+}
+
+/// How a completion ended
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Completion {
+    /// Every critical pair of the rules joins
+    Confluent(RuleSet),
+    /// A critical pair did not join, and its sides could not be ordered
+    Unorientable {
+        rules: RuleSet,
+        pair: Box<CriticalPair>,
+    },
+    /// The most rules to add were added, and some critical pairs still did not join
+    OutOfBudget(RuleSet),
+}
+
+/// Adds rules to a set of rules until all of its critical pairs join, Knuth-Bendix style.
+///
+/// Each critical pair that does not join within `join_budget` sequences of terms is rewritten as
+/// far as `join_budget` reductions go, and unless both sides end up the same they are turned into
+/// a rule from the greater side to the lesser side under the given order. No more than
+/// `max_rules` rules are added.
+#[must_use]
+pub fn complete<I: Interner, O: Order<I> + ?Sized>(
+    engine: &Engine<I>,
+    rules: &RuleSet,
+    order: &O,
+    join_budget: usize,
+    max_rules: usize,
+) -> Completion {
+    let mut rules = rules.clone();
+    let mut added = 0;
+    let config = Config {
+        fuel: Some(join_budget),
+        ..Config::new()
+    };
+    loop {
+        let pairs = confluence::check(engine, &rules, join_budget);
+        if pairs.is_empty() {
+            return Completion::Confluent(rules);
+        }
+        let before = added;
+        for pair in pairs {
+            let normalize = |terms: &Vector<Term>| {
+                rewrite_with(engine, &rules, terms.clone(), &config, &mut ()).terms
+            };
+            let left = normalize(&pair.left_reduct);
+            let right = normalize(&pair.right_reduct);
+            let (redex, reduction) = match order.compare(engine, &left, &right) {
+                Some(Ordering::Equal) => continue,
+                Some(Ordering::Greater) => (left, right),
+                Some(Ordering::Less) => (right, left),
+                None => {
+                    let pair = Box::new(pair);
+                    return Completion::Unorientable { rules, pair };
+                }
+            };
+            if added == max_rules {
+                return Completion::OutOfBudget(rules);
+            }
+            rules.insert(Rule { redex, reduction });
+            added += 1;
+        }
+        // Every pair that did not join within the budget still had equal normal forms
+        if added == before {
+            return Completion::Confluent(rules);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse, pretty};
+
+    #[test]
+    fn order_test() {
+        let engine = Engine::new();
+        let compare = |order: &LengthLexicographic, left, right| {
+            let left = parse::terms(&engine, left).unwrap();
+            let right = parse::terms(&engine, right).unwrap();
+            order.compare(&engine, &left, &right)
+        };
+        let order = LengthLexicographic::new();
+        assert_eq!(compare(&order, "a b", "c"), Some(Ordering::Greater));
+        assert_eq!(compare(&order, "a b", "a c"), Some(Ordering::Less));
+        assert_eq!(compare(&order, "(a)", "a b"), Some(Ordering::Greater));
+        let order = LengthLexicographic {
+            precedence: vec![engine.get_or_intern("c".to_owned())],
+        };
+        assert_eq!(compare(&order, "a b", "c b"), Some(Ordering::Greater));
+
+        let rule = parse::rule(&engine, "($A) ($B) f = ($A) ($A).").unwrap();
+        let order = LengthLexicographic::new();
+        assert_eq!(order.compare(&engine, &rule.redex, &rule.reduction), None);
+    }
+
+    #[test]
+    fn complete_test() {
+        let engine = Engine::new();
+        let rules = parse::rules(&engine, "x y = z. y w = v.").unwrap();
+        match complete(&engine, &rules, &LengthLexicographic::new(), 100, 10) {
+            Completion::Confluent(rules) => assert_eq!(
+                pretty::rules(&engine, rules),
+                "x y = z.\ny w = v.\nz w = x v.\n"
+            ),
+            completion => panic!("expected a confluent completion, got {completion:?}"),
+        }
+
+        let rules = parse::rules(&engine, "a b a = b a b.").unwrap();
+        assert!(matches!(
+            complete(&engine, &rules, &LengthLexicographic::new(), 100, 3),
+            Completion::OutOfBudget(rules) if rules.len() == 4
+        ));
+
+        let rules = parse::rules(&engine, "($A) ($B) f = ($A) ($A). ($A) ($B) f = ($B).").unwrap();
+        assert!(matches!(
+            complete(&engine, &rules, &LengthLexicographic::new(), 100, 10),
+            Completion::Unorientable { .. }
+        ));
+    }
+}
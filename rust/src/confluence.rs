@@ -0,0 +1,300 @@
+use crate::{pattern, steps, Engine, Interner, Primitive, Rule, RuleSet, Term};
+use im::{vector, HashSet, Vector};
+use std::collections::VecDeque;
+
+/// Where one side of a critical pair comes from
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Source {
+    /// The user rule with the given id
+    Rule(usize),
+    /// The built-in reduction of a primitive
+    Primitive(Primitive),
+}
+
+/// Two ways of reducing the same sequence of terms, by reducing two overlapping redexes.
+///
+/// The terms can hold sequence variables, which stand for any contents of the quote they are in.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct CriticalPair {
+    pub left: Source,
+    pub right: Source,
+    /// How many terms into the redex of the left side the redex of the right side starts
+    pub offset: usize,
+    /// The sequence of terms both redexes are in
+    pub overlap: Vector<Term>,
+    /// The overlap after reducing the redex of the left side
+    pub left_reduct: Vector<Term>,
+    /// The overlap after reducing the redex of the right side
+    pub right_reduct: Vector<Term>,
+}
+
+/// The built-in reductions of the primitives, written as rules with generated variables
+#[must_use]
+pub fn primitive_rules<I: Interner>(engine: &Engine<I>) -> Vec<(Primitive, Rule)> {
+    let (a, b) = (engine.generate("$A"), engine.generate("$B"));
+    let var = |spur| Term::make_var(engine, spur);
+    let seq = |spur| Term::make_seq_var(engine, spur);
+    let quote = |terms| Term::make_quote(engine, terms);
+    let prim = |primitive| Term::make_prim(engine, primitive);
+    let rule = |redex, reduction| Rule { redex, reduction };
+    vec![
+        (
+            Primitive::Copy,
+            rule(
+                vector![var(a), prim(Primitive::Copy)],
+                vector![var(a), var(a)],
+            ),
+        ),
+        (
+            Primitive::Discard,
+            rule(vector![var(a), prim(Primitive::Discard)], vector![]),
+        ),
+        (
+            Primitive::Wrap,
+            rule(
+                vector![var(a), prim(Primitive::Wrap)],
+                vector![quote(vector![var(a)])],
+            ),
+        ),
+        (
+            Primitive::Unwrap,
+            rule(
+                vector![quote(vector![seq(a)]), prim(Primitive::Unwrap)],
+                vector![seq(a)],
+            ),
+        ),
+        (
+            Primitive::Swap,
+            rule(
+                vector![var(a), var(b), prim(Primitive::Swap)],
+                vector![var(b), var(a)],
+            ),
+        ),
+        (
+            Primitive::Combine,
+            rule(
+                vector![
+                    quote(vector![seq(a)]),
+                    quote(vector![seq(b)]),
+                    prim(Primitive::Combine)
+                ],
+                vector![quote(vector![seq(a), seq(b)])],
+            ),
+        ),
+    ]
+}
+
+/// Finds the critical pairs of a set of rules, between any two user rules and between a user rule
+/// and a primitive
+#[must_use]
+pub fn critical_pairs<I: Interner>(engine: &Engine<I>, rules: &RuleSet) -> Vec<CriticalPair> {
+    let sources: Vec<_> = rules
+        .iter()
+        .map(|(id, rule)| (Source::Rule(id), rule.clone()))
+        .chain(
+            primitive_rules(engine)
+                .into_iter()
+                .map(|(primitive, rule)| (Source::Primitive(primitive), rule)),
+        )
+        .collect();
+    let mut pairs = Vec::new();
+    for (left, left_rule) in &sources {
+        for (right, right_rule) in &sources {
+            if matches!((left, right), (Source::Primitive(_), Source::Primitive(_))) {
+                continue;
+            }
+            pairs.extend(overlap(engine, *left, left_rule, *right, right_rule));
+        }
+    }
+    pairs
+}
+
+fn overlap<I: Interner>(
+    engine: &Engine<I>,
+    left: Source,
+    left_rule: &Rule,
+    right: Source,
+    right_rule: &Rule,
+) -> Vec<CriticalPair> {
+    let right_rule = pattern::rename(engine, right_rule, &pattern::variables(&left_rule.redex));
+    let (l1, r1) = (
+        pattern::desugar(engine, &left_rule.redex),
+        pattern::desugar(engine, &left_rule.reduction),
+    );
+    let (l2, r2) = (
+        pattern::desugar(engine, &right_rule.redex),
+        pattern::desugar(engine, &right_rule.reduction),
+    );
+    if l1.is_empty() || l2.is_empty() {
+        return vec![];
+    }
+    let mut pairs = Vec::new();
+    for offset in 0..l1.len() {
+        // Overlapping a redex with itself at its own position gives the same reduct twice
+        if offset == 0 && left == right {
+            continue;
+        }
+        let shared = (l1.len() - offset).min(l2.len());
+        let overlap = l1.clone() + l2.skip(shared);
+        let left_reduct = r1.clone() + l2.skip(shared);
+        let right_reduct = l1.take(offset) + r2.clone() + l1.skip(offset + shared);
+        for bindings in pattern::unify(engine, &l1.skip(offset).take(shared), &l2.take(shared)) {
+            let resolve = |terms| pattern::resolve(engine, terms, &bindings);
+            pairs.push(CriticalPair {
+                left,
+                right,
+                offset,
+                overlap: resolve(&overlap),
+                left_reduct: resolve(&left_reduct),
+                right_reduct: resolve(&right_reduct),
+            });
+        }
+    }
+    pairs
+}
+
+/// Checks whether two sequences of terms rewrite to a common sequence of terms, looking at no more
+/// than `budget` sequences of terms reachable from each of them
+#[must_use]
+pub fn joinable<I: Interner>(
+    engine: &Engine<I>,
+    rules: &RuleSet,
+    left: &Vector<Term>,
+    right: &Vector<Term>,
+    budget: usize,
+) -> bool {
+    let left = reachable(engine, rules, left, budget);
+    let right = reachable(engine, rules, right, budget);
+    right.iter().any(|terms| left.contains(terms))
+}
+
+fn reachable<I: Interner>(
+    engine: &Engine<I>,
+    rules: &RuleSet,
+    start: &Vector<Term>,
+    budget: usize,
+) -> HashSet<Vector<Term>> {
+    let mut seen = HashSet::unit(start.clone());
+    let mut queue = VecDeque::from([start.clone()]);
+    while let Some(terms) = queue.pop_front() {
+        for step in steps(engine, rules, &terms) {
+            if seen.len() >= budget {
+                return seen;
+            }
+            if !seen.contains(&step.after) {
+                seen.insert(step.after.clone());
+                queue.push_back(step.after);
+            }
+        }
+    }
+    seen
+}
+
+/// Finds the critical pairs of a set of rules that could not be joined within the budget, as
+/// counterexamples to the rules giving the same normal form whichever redex is reduced first.
+///
+/// The sequence variables in a critical pair are replaced by words of the same name before
+/// rewriting, so each pair is checked for a typical instance.
+#[must_use]
+pub fn check<I: Interner>(engine: &Engine<I>, rules: &RuleSet, budget: usize) -> Vec<CriticalPair> {
+    critical_pairs(engine, rules)
+        .into_iter()
+        .filter(|pair| {
+            let left = pattern::skolemize(engine, &pair.left_reduct);
+            let right = pattern::skolemize(engine, &pair.right_reduct);
+            !joinable(engine, rules, &left, &right, budget)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse, pretty, validate};
+
+    fn unjoinable(engine: &Engine, input: &str) -> Vec<(Source, Source, String, String, String)> {
+        let rules = parse::rules(engine, input).unwrap();
+        check(engine, &rules, 100)
+            .into_iter()
+            .map(|pair| {
+                (
+                    pair.left,
+                    pair.right,
+                    pretty::terms(engine, pair.overlap),
+                    pretty::terms(engine, pair.left_reduct),
+                    pretty::terms(engine, pair.right_reduct),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn rule_pair_test() {
+        let engine = Engine::new();
+        assert_eq!(unjoinable(&engine, "a = b. c d = e."), vec![]);
+        assert_eq!(unjoinable(&engine, "a b = c. b = d. a d = c."), vec![]);
+        assert_eq!(
+            unjoinable(&engine, "a b = c. b c = d."),
+            vec![(
+                Source::Rule(0),
+                Source::Rule(1),
+                "a b c".to_owned(),
+                "c c".to_owned(),
+                "a d".to_owned()
+            )]
+        );
+        assert_eq!(
+            unjoinable(&engine, "x x = y."),
+            vec![(
+                Source::Rule(0),
+                Source::Rule(0),
+                "x x x".to_owned(),
+                "y x".to_owned(),
+                "x y".to_owned()
+            )]
+        );
+    }
+
+    #[test]
+    fn primitive_pair_test() {
+        let engine = Engine::new();
+        assert_eq!(
+            unjoinable(&engine, "+ foo = bar."),
+            vec![(
+                Source::Primitive(Primitive::Copy),
+                Source::Rule(0),
+                "($A) + foo".to_owned(),
+                "($A) ($A) foo".to_owned(),
+                "($A) bar".to_owned()
+            )]
+        );
+        assert_eq!(unjoinable(&engine, "($A) ($B) swap = ($B) ($A)."), vec![]);
+        assert_eq!(unjoinable(&engine, "+ - = ."), vec![]);
+        assert_eq!(
+            critical_pairs(&engine, &parse::rules(&engine, "+ - = .").unwrap()).len(),
+            1
+        );
+    }
+
+    #[test]
+    fn frozen_test() {
+        let engine = Engine::new();
+        let rules = parse::rules(&engine, "($A) ($B) swap = ($B) ($A). + foo = bar.").unwrap();
+        let overlapping = parse::rules(&engine, "($A) x y = y. ($A) x = z.").unwrap();
+        let engine = engine.freeze();
+        let pairs = check(&engine, &rules, 100);
+        assert_eq!(pairs.len(), 1);
+        assert_eq!(
+            pretty::terms(&engine, pairs[0].overlap.clone()),
+            "($A) + foo"
+        );
+        assert_eq!(
+            validate::rules(&engine, &overlapping),
+            vec![validate::Issue::Overlap {
+                left: 0,
+                right: 1,
+                offset: 0
+            }]
+        );
+    }
+}
@@ -0,0 +1,401 @@
+use crate::{graph, Engine, Interner, Primitive, Rule, RuleSet, Term};
+use im::{HashMap, HashSet, Vector};
+use lasso::Spur;
+use std::fmt;
+
+/// How many quotes a sequence of terms takes from before it and gives back in their place
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Default)]
+pub struct Effect {
+    pub inputs: usize,
+    pub outputs: usize,
+}
+
+impl Effect {
+    #[must_use]
+    pub const fn new(inputs: usize, outputs: usize) -> Self {
+        Self { inputs, outputs }
+    }
+
+    /// The effect of running terms with this effect and then terms with the other effect
+    #[must_use]
+    pub const fn then(self, other: Self) -> Self {
+        Self {
+            inputs: self.inputs + other.inputs.saturating_sub(self.outputs),
+            outputs: other.outputs + self.outputs.saturating_sub(other.inputs),
+        }
+    }
+
+    /// Whether terms with this effect and terms with the other effect change the number of quotes
+    /// by as much, so that one of the effects only takes and gives back more quotes than the other
+    #[must_use]
+    pub const fn fits(self, other: Self) -> bool {
+        self.inputs + other.outputs == other.inputs + self.outputs
+    }
+}
+
+impl fmt::Display for Effect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} -> {}", self.inputs, self.outputs)
+    }
+}
+
+/// Why the effect of a sequence of terms could not be found
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Unknown {
+    /// No rule that the effect can be found from defines the word, and it has no declared arity
+    Undefined(Spur),
+    /// The effect of the word depends on itself
+    Recursive(Spur),
+    /// A quote whose contents are not known is unwrapped, or unwrapping quotes does not stop
+    Dynamic,
+    /// A sequence variable is unwrapped, which could stand for any terms
+    Variable(Spur),
+}
+
+/// A rule whose redex and reduction have effects that do not fit together
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct Mismatch {
+    pub rule: usize,
+    pub redex: Effect,
+    pub reduction: Effect,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "rule {} has a redex with effect {} but a reduction with effect {}",
+            self.rule, self.redex, self.reduction
+        )
+    }
+}
+
+/// How deeply quotes whose contents are known can be unwrapped inside of each other
+const MAX_UNWRAPS: usize = 64;
+
+/// Finds effects, remembering the effects of the words it already looked at
+struct Inference<'e, 'r, I> {
+    engine: &'e Engine<I>,
+    rules: &'r RuleSet,
+    words: HashMap<Spur, Result<Effect, Unknown>>,
+    visiting: HashSet<Spur>,
+}
+
+/// A stack of quotes while running terms, with the contents of the quotes that are known
+struct Stack {
+    quotes: Vec<Option<Vector<Term>>>,
+    inputs: usize,
+}
+
+impl Stack {
+    const fn new() -> Self {
+        Self {
+            quotes: Vec::new(),
+            inputs: 0,
+        }
+    }
+
+    /// Takes the top quote, taking it from before the terms if the stack is empty
+    fn pop(&mut self) -> Option<Vector<Term>> {
+        self.quotes.pop().unwrap_or_else(|| {
+            self.inputs += 1;
+            None
+        })
+    }
+
+    fn push(&mut self, quote: Option<Vector<Term>>) {
+        self.quotes.push(quote);
+    }
+
+    const fn effect(&self) -> Effect {
+        Effect::new(self.inputs, self.quotes.len())
+    }
+}
+
+impl<I: Interner> Inference<'_, '_, I> {
+    fn terms(&mut self, terms: &Vector<Term>) -> Result<Effect, Unknown> {
+        let mut stack = Stack::new();
+        self.run(&mut stack, terms, 0)?;
+        Ok(stack.effect())
+    }
+
+    fn run(
+        &mut self,
+        stack: &mut Stack,
+        terms: &Vector<Term>,
+        depth: usize,
+    ) -> Result<(), Unknown> {
+        for term in terms {
+            match term {
+                Term::Quote(inner) => stack.push(Some(inner.terms().clone())),
+                Term::Var(_) => stack.push(None),
+                Term::SeqVar(var) => return Err(Unknown::Variable(*var)),
+                Term::Word(word) => {
+                    let effect = self.word(*word)?;
+                    for _ in 0..effect.inputs {
+                        stack.pop();
+                    }
+                    for _ in 0..effect.outputs {
+                        stack.push(None);
+                    }
+                }
+                Term::Prim(primitive) => self.primitive(stack, *primitive, depth)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn primitive(
+        &mut self,
+        stack: &mut Stack,
+        primitive: Primitive,
+        depth: usize,
+    ) -> Result<(), Unknown> {
+        match primitive {
+            Primitive::Copy => {
+                let a = stack.pop();
+                stack.push(a.clone());
+                stack.push(a);
+            }
+            Primitive::Discard => {
+                stack.pop();
+            }
+            Primitive::Wrap => {
+                let a = stack.pop();
+                stack.push(a.map(|inner| Vector::unit(Term::make_quote(self.engine, inner))));
+            }
+            Primitive::Unwrap => match stack.pop() {
+                Some(inner) if depth < MAX_UNWRAPS => self.run(stack, &inner, depth + 1)?,
+                _ => return Err(Unknown::Dynamic),
+            },
+            Primitive::Swap => {
+                let b = stack.pop();
+                let a = stack.pop();
+                stack.push(b);
+                stack.push(a);
+            }
+            Primitive::Combine => {
+                let b = stack.pop();
+                let a = stack.pop();
+                stack.push(a.zip(b).map(|(a, b)| a + b));
+            }
+        }
+        Ok(())
+    }
+
+    /// Finds the effect of a word from its declared arity, or else from the first of its rules
+    /// it can be found from
+    fn word(&mut self, word: Spur) -> Result<Effect, Unknown> {
+        if let Some((inputs, outputs)) = self.engine.symbol(word).and_then(|symbol| symbol.arity) {
+            return Ok(Effect::new(inputs, outputs));
+        }
+        if let Some(effect) = self.words.get(&word) {
+            return *effect;
+        }
+        if self.visiting.insert(word).is_some() {
+            return Err(Unknown::Recursive(word));
+        }
+        let mut effect = None;
+        for (_, rule) in self.rules.by_head(word) {
+            if rule.redex.back() != Some(&Term::Word(word)) {
+                continue;
+            }
+            match self.defined(rule) {
+                Ok(defined) => {
+                    effect = Some(Ok(defined));
+                    break;
+                }
+                Err(unknown) => {
+                    effect.get_or_insert(Err(unknown));
+                }
+            }
+        }
+        let effect = effect.unwrap_or(Err(Unknown::Undefined(word)));
+        self.visiting.remove(&word);
+        self.words.insert(word, effect);
+        effect
+    }
+
+    /// Finds the effect a rule gives the word it defines, which is the last term of its redex
+    fn defined(&mut self, rule: &Rule) -> Result<Effect, Unknown> {
+        let before = self.terms(&rule.redex.take(rule.redex.len() - 1))?;
+        let reduction = self.terms(&rule.reduction)?;
+        let inputs = (reduction.inputs + before.outputs).saturating_sub(before.inputs);
+        let left = before.outputs.saturating_sub(inputs);
+        let outputs = reduction.outputs.saturating_sub(left);
+        Ok(Effect::new(inputs, outputs))
+    }
+}
+
+/// Finds the effect of a sequence of terms, using the arities declared in the engine and the
+/// rules that define the words
+///
+/// # Errors
+///
+/// Returns an `Err` if the effect depends on a word whose effect is not known, or on the contents
+/// of a quote that are not known
+pub fn terms<I: Interner>(
+    engine: &Engine<I>,
+    rules: &RuleSet,
+    terms: &Vector<Term>,
+) -> Result<Effect, Unknown> {
+    inference(engine, rules).terms(terms)
+}
+
+/// Finds the effect of a word, from its declared arity or from the first rule defining it whose
+/// effect is known
+///
+/// # Errors
+///
+/// Returns an `Err` if the effect of the word is not known
+pub fn word<I: Interner>(
+    engine: &Engine<I>,
+    rules: &RuleSet,
+    word: Spur,
+) -> Result<Effect, Unknown> {
+    inference(engine, rules).word(word)
+}
+
+/// Finds the effects of the redex and the reduction of a rule
+///
+/// # Errors
+///
+/// Returns an `Err` if the effect of either side is not known
+pub fn rule<I: Interner>(
+    engine: &Engine<I>,
+    rules: &RuleSet,
+    rule: &Rule,
+) -> Result<(Effect, Effect), Unknown> {
+    let mut inference = inference(engine, rules);
+    Ok((
+        inference.terms(&rule.redex)?,
+        inference.terms(&rule.reduction)?,
+    ))
+}
+
+/// Finds the rules whose redex and reduction change the number of quotes by different amounts,
+/// leaving out the rules where either effect is not known.
+///
+/// Effects that only differ in how many more quotes they take and give back fit together, like
+/// those of `~ ~` and nothing.
+#[must_use]
+pub fn check<I: Interner>(engine: &Engine<I>, rules: &RuleSet) -> Vec<Mismatch> {
+    let mut inference = inference(engine, rules);
+    rules
+        .iter()
+        .filter_map(|(id, rule)| {
+            let redex = inference.terms(&rule.redex).ok()?;
+            let reduction = inference.terms(&rule.reduction).ok()?;
+            (!redex.fits(reduction)).then_some(Mismatch {
+                rule: id,
+                redex,
+                reduction,
+            })
+        })
+        .collect()
+}
+
+/// Starts finding effects by finding the effects of all the defined words, with the words each
+/// word depends on first, so that finding the effect of a word only recurses into the words of
+/// its own recursive group
+fn inference<'e, 'r, I: Interner>(
+    engine: &'e Engine<I>,
+    rules: &'r RuleSet,
+) -> Inference<'e, 'r, I> {
+    let mut inference = Inference {
+        engine,
+        rules,
+        words: HashMap::new(),
+        visiting: HashSet::new(),
+    };
+    for component in graph::mentions(rules).components() {
+        for word in component {
+            let _ = inference.word(word);
+        }
+    }
+    inference
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+    use std::fmt::Write;
+
+    fn effect_of(engine: &Engine, rules: &RuleSet, input: &str) -> Result<Effect, Unknown> {
+        terms(engine, rules, &parse::terms(engine, input).unwrap())
+    }
+
+    #[test]
+    fn primitive_test() {
+        let engine = Engine::new();
+        let rules = RuleSet::new();
+        let effect = |input| effect_of(&engine, &rules, input);
+        assert_eq!(effect("~"), Ok(Effect::new(2, 2)));
+        assert_eq!(effect(","), Ok(Effect::new(2, 1)));
+        assert_eq!(effect("+ -"), Ok(Effect::new(1, 1)));
+        assert_eq!(effect("(+) <"), Ok(Effect::new(1, 2)));
+        assert_eq!(effect("(x) > <"), Ok(Effect::new(0, 1)));
+        assert_eq!(effect("(-) (-) , <"), Ok(Effect::new(2, 0)));
+        assert_eq!(effect("(a) ~ <"), Err(Unknown::Dynamic));
+        assert_eq!(effect("(+ <) + <"), Err(Unknown::Dynamic));
+        let a = engine.get("a").unwrap();
+        assert_eq!(effect("(a) <"), Err(Unknown::Undefined(a)));
+        engine.update_symbol(a, |symbol| symbol.arity = Some((0, 1)));
+        assert_eq!(effect("(a) < a"), Ok(Effect::new(0, 2)));
+    }
+
+    #[test]
+    fn word_test() {
+        let engine = Engine::new();
+        let rules = parse::rules(
+            &engine,
+            "($A) ($B) swap = ($B) ($A). ($A) dup = ($A) ($A). loop = loop. twice = dup ,. x = y.",
+        )
+        .unwrap();
+        let effect = |input| effect_of(&engine, &rules, input);
+        assert_eq!(effect("swap"), Ok(Effect::new(2, 2)));
+        assert_eq!(effect("dup"), Ok(Effect::new(1, 2)));
+        assert_eq!(effect("twice"), Ok(Effect::new(1, 1)));
+        assert_eq!(effect("(a) (b) swap dup"), Ok(Effect::new(0, 3)));
+        let looping = engine.get("loop").unwrap();
+        assert_eq!(effect("loop"), Err(Unknown::Recursive(looping)));
+        let y = engine.get("y").unwrap();
+        assert_eq!(effect("x"), Err(Unknown::Undefined(y)));
+
+        let chain = (0..3000).fold(String::from("($A) w3000 = ."), |mut chain, n| {
+            let _ = write!(chain, " w{n} = w{}.", n + 1);
+            chain
+        });
+        let rules = parse::rules(&engine, &chain).unwrap();
+        assert_eq!(effect_of(&engine, &rules, "w0"), Ok(Effect::new(1, 0)));
+    }
+
+    #[test]
+    fn check_test() {
+        let engine = Engine::new();
+        let rules = parse::rules(
+            &engine,
+            "($A) f = ($A) ($A). ($A) ($B) f = ($A). g = (x) -. + - = . ~ ~ = . \
+             ($A) ($B) swap = ($B) ($A). swap swap = .",
+        )
+        .unwrap();
+        assert_eq!(
+            check(&engine, &rules),
+            vec![Mismatch {
+                rule: 1,
+                redex: Effect::new(0, 3),
+                reduction: Effect::new(0, 1),
+            }]
+        );
+        let rule = parse::rule(&engine, "h = (x) (y) ~ -.").unwrap();
+        assert_eq!(
+            super::rule(&engine, &rules, &rule),
+            Err(Unknown::Undefined(engine.get("h").unwrap()))
+        );
+        assert_eq!(
+            super::rule(&engine, &rules, &rules.get(2).unwrap().clone()),
+            Ok((Effect::new(0, 0), Effect::new(0, 0)))
+        );
+    }
+}
@@ -0,0 +1,257 @@
+use crate::{Engine, Interner, RuleSet, Term};
+use im::{HashMap, HashSet, Vector};
+use lasso::Spur;
+use std::fmt::Write;
+
+/// Which words each word is defined in terms of: a word depends on every word in the reductions
+/// of the rules headed by it, including inside of quotes
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Graph {
+    pub dependencies: HashMap<Spur, HashSet<Spur>>,
+}
+
+impl Graph {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Every word in the graph, whether it depends on words or is depended on, ordered by key
+    #[must_use]
+    pub fn words(&self) -> Vec<Spur> {
+        let mut words: Vec<_> = self
+            .dependencies
+            .iter()
+            .flat_map(|(word, dependencies)| dependencies.iter().chain([word]))
+            .copied()
+            .collect();
+        words.sort();
+        words.dedup();
+        words
+    }
+
+    /// The words a word depends on, ordered by key
+    #[must_use]
+    pub fn dependencies(&self, word: Spur) -> Vec<Spur> {
+        let mut dependencies: Vec<_> = self
+            .dependencies
+            .get(&word)
+            .into_iter()
+            .flatten()
+            .copied()
+            .collect();
+        dependencies.sort();
+        dependencies
+    }
+
+    /// Splits the words into groups that depend on each other, with Tarjan's algorithm. Every group
+    /// comes after the groups it depends on.
+    #[must_use]
+    pub fn components(&self) -> Vec<Vec<Spur>> {
+        struct Tarjan {
+            index: HashMap<Spur, usize>,
+            low: HashMap<Spur, usize>,
+            stack: Vec<Spur>,
+            on_stack: HashSet<Spur>,
+            components: Vec<Vec<Spur>>,
+        }
+        impl Tarjan {
+            fn enter(&mut self, word: Spur) {
+                let index = self.index.len();
+                self.index.insert(word, index);
+                self.low.insert(word, index);
+                self.stack.push(word);
+                self.on_stack.insert(word);
+            }
+
+            fn lower(&mut self, word: Spur, to: usize) {
+                let low = self.low[&word].min(to);
+                self.low.insert(word, low);
+            }
+
+            fn leave(&mut self, word: Spur) {
+                if self.low[&word] == self.index[&word] {
+                    let position = self.stack.iter().rposition(|w| *w == word).unwrap_or(0);
+                    let component = self.stack.split_off(position);
+                    for word in &component {
+                        self.on_stack.remove(word);
+                    }
+                    self.components.push(component);
+                }
+            }
+        }
+        let mut tarjan = Tarjan {
+            index: HashMap::new(),
+            low: HashMap::new(),
+            stack: Vec::new(),
+            on_stack: HashSet::new(),
+            components: Vec::new(),
+        };
+        for root in self.words() {
+            if tarjan.index.contains_key(&root) {
+                continue;
+            }
+            // The words being visited, with the dependencies each has left to visit, kept on the
+            // heap so that long chains of words do not overflow the stack
+            tarjan.enter(root);
+            let mut visiting = vec![(root, self.dependencies(root).into_iter())];
+            while let Some((word, dependencies)) = visiting.last_mut() {
+                let word = *word;
+                if let Some(dependency) = dependencies.next() {
+                    if !tarjan.index.contains_key(&dependency) {
+                        tarjan.enter(dependency);
+                        visiting.push((dependency, self.dependencies(dependency).into_iter()));
+                    } else if tarjan.on_stack.contains(&dependency) {
+                        tarjan.lower(word, tarjan.index[&dependency]);
+                    }
+                } else {
+                    visiting.pop();
+                    if let Some((parent, _)) = visiting.last() {
+                        tarjan.lower(*parent, tarjan.low[&word]);
+                    }
+                    tarjan.leave(word);
+                }
+            }
+        }
+        tarjan.components
+    }
+
+    /// The groups of words that are recursive, either because there is more than one word in the
+    /// group or because the word depends on itself
+    #[must_use]
+    pub fn recursive(&self) -> Vec<Vec<Spur>> {
+        self.components()
+            .into_iter()
+            .filter(|component| match component.as_slice() {
+                [word] => self
+                    .dependencies
+                    .get(word)
+                    .is_some_and(|d| d.contains(word)),
+                _ => true,
+            })
+            .collect()
+    }
+
+    /// Exports the graph in the Graphviz DOT language, with each recursive group of more than one
+    /// word in a cluster
+    #[must_use]
+    pub fn dot<I: Interner>(&self, engine: &Engine<I>) -> String {
+        let name = |word: &Spur| {
+            let name = engine.resolve(word);
+            format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+        };
+        let mut dot = String::from("digraph {\n");
+        let recursive = self.recursive();
+        let groups = recursive.iter().filter(|group| group.len() > 1);
+        for (index, group) in groups.enumerate() {
+            let _ = writeln!(dot, "    subgraph cluster_{index} {{");
+            for word in group {
+                let _ = writeln!(dot, "        {};", name(word));
+            }
+            dot.push_str("    }\n");
+        }
+        for word in self.words() {
+            let _ = writeln!(dot, "    {};", name(&word));
+            for dependency in self.dependencies(word) {
+                let _ = writeln!(dot, "    {} -> {};", name(&word), name(&dependency));
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Adds the words in some terms to a set, including words inside of quotes
+pub(crate) fn words(terms: &Vector<Term>, found: &mut HashSet<Spur>) {
+    for term in terms {
+        match term {
+            Term::Word(word) => {
+                found.insert(*word);
+            }
+            Term::Quote(inner) => words(inner, found),
+            _ => {}
+        }
+    }
+}
+
+/// Builds the graph of which words the rules define each word in terms of
+#[must_use]
+pub fn rules(rules: &RuleSet) -> Graph {
+    let mut dependencies = HashMap::<Spur, HashSet<Spur>>::new();
+    for (_, rule) in rules.iter() {
+        if let Some(head) = rule.head() {
+            words(&rule.reduction, dependencies.entry(head).or_default());
+        }
+    }
+    Graph { dependencies }
+}
+
+/// Builds the graph of which words the rules of each word mention, in their redexes as well as
+/// their reductions
+pub(crate) fn mentions(rules: &RuleSet) -> Graph {
+    let mut dependencies = HashMap::<Spur, HashSet<Spur>>::new();
+    for (_, rule) in rules.iter() {
+        if let Some(head) = rule.head() {
+            let found = dependencies.entry(head).or_default();
+            words(&rule.redex, found);
+            words(&rule.reduction, found);
+        }
+    }
+    Graph { dependencies }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn components_test() {
+        let engine = Engine::new();
+        let rules = parse::rules(
+            &engine,
+            "even = (odd) <. odd = even. loop = loop. twice = dup ,. ($A) dup = ($A) ($A).",
+        )
+        .unwrap();
+        let graph = super::rules(&rules);
+        let names = |words: &[Spur]| {
+            let mut names: Vec<_> = words
+                .iter()
+                .map(|w| engine.resolve(w).to_string())
+                .collect();
+            names.sort_unstable();
+            names.join(" ")
+        };
+        let components: Vec<_> = graph.components().iter().map(|c| names(c)).collect();
+        let position = |name| components.iter().position(|c| c == name).unwrap();
+        assert_eq!(components.len(), 4);
+        assert!(position("dup") < position("twice"));
+        assert!(components.contains(&"even odd".to_owned()));
+        let recursive: Vec<_> = graph.recursive().iter().map(|c| names(c)).collect();
+        assert_eq!(recursive.len(), 2);
+        assert!(recursive.contains(&"even odd".to_owned()));
+        assert!(recursive.contains(&"loop".to_owned()));
+
+        let chain = (0..3000).fold(String::new(), |mut chain, n| {
+            let _ = write!(chain, "w{n} = w{}. ", n + 1);
+            chain
+        });
+        let graph = super::rules(&parse::rules(&engine, &chain).unwrap());
+        let components = graph.components();
+        assert_eq!(components.len(), 3001);
+        assert_eq!(components[0], vec![engine.get("w3000").unwrap()]);
+    }
+
+    #[test]
+    fn dot_test() {
+        let engine = Engine::new();
+        let rules = parse::rules(&engine, "a = (b) a. b = a c.").unwrap();
+        let graph = super::rules(&rules);
+        let dot = graph.dot(&engine);
+        assert!(dot.starts_with("digraph {\n    subgraph cluster_0 {\n"));
+        assert!(dot.contains("    \"a\" -> \"b\";\n"));
+        assert!(dot.contains("    \"b\" -> \"a\";\n"));
+        assert!(dot.contains("    \"c\";\n"));
+        assert!(dot.ends_with("}\n"));
+    }
+}
@@ -0,0 +1,128 @@
+use lasso::{Rodeo, RodeoReader, Spur, ThreadedRodeo};
+use std::cell::{Ref, RefCell};
+use std::fmt::Display;
+use std::ops::Deref;
+
+/// Where an engine keeps its words.
+///
+/// Words are named by lasso spurs, but an interner can keep its words however it likes, and make
+/// its spurs from its own indices with `lasso::Key::try_from_usize`. The indices have to stay
+/// below 2^24, since an engine keeps which engine interned a word in the bits of a spur above
+/// those. An engine can not intern a word that an interner gives a bigger index.
+pub trait Interner {
+    /// What a word is borrowed as
+    type Word<'a>: Deref<Target = str> + Display
+    where
+        Self: 'a;
+
+    /// Interns a word, or gives back `None` if the interner can not take it
+    fn get_or_intern(&self, s: String) -> Option<Spur>;
+
+    /// Finds the spur of a word that was already interned
+    fn get(&self, s: &str) -> Option<Spur>;
+
+    /// Finds the word of a spur, panicking if the interner did not intern it
+    fn resolve(&self, spur: &Spur) -> Self::Word<'_>;
+
+    /// What the interner turns into once it can not take new words
+    type Frozen: Interner;
+
+    /// Turns the interner into one that can not take new words
+    fn freeze(self) -> Self::Frozen;
+}
+
+impl Interner for ThreadedRodeo {
+    type Word<'a> = &'a str;
+    type Frozen = RodeoReader;
+
+    fn get_or_intern(&self, s: String) -> Option<Spur> {
+        self.try_get_or_intern(s).ok()
+    }
+
+    fn get(&self, s: &str) -> Option<Spur> {
+        self.get(s)
+    }
+
+    fn resolve(&self, spur: &Spur) -> &str {
+        self.resolve(spur)
+    }
+
+    fn freeze(self) -> Self::Frozen {
+        self.into_reader()
+    }
+}
+
+/// A single-threaded interner, which does not pay for locking but can not be shared between threads
+impl Interner for RefCell<Rodeo> {
+    type Word<'a> = Ref<'a, str>;
+    type Frozen = RodeoReader;
+
+    fn get_or_intern(&self, s: String) -> Option<Spur> {
+        self.borrow_mut().try_get_or_intern(s).ok()
+    }
+
+    fn get(&self, s: &str) -> Option<Spur> {
+        self.borrow().get(s)
+    }
+
+    fn resolve(&self, spur: &Spur) -> Ref<'_, str> {
+        Ref::map(self.borrow(), |rodeo| rodeo.resolve(spur))
+    }
+
+    fn freeze(self) -> Self::Frozen {
+        self.into_inner().into_reader()
+    }
+}
+
+/// A frozen interner, which can only look up the words that were interned before it was frozen
+impl Interner for RodeoReader {
+    type Word<'a> = &'a str;
+    type Frozen = Self;
+
+    fn get_or_intern(&self, s: String) -> Option<Spur> {
+        self.get(s)
+    }
+
+    fn get(&self, s: &str) -> Option<Spur> {
+        self.get(s)
+    }
+
+    fn resolve(&self, spur: &Spur) -> &str {
+        self.resolve(spur)
+    }
+
+    fn freeze(self) -> Self {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse, pretty, rewrite, Engine};
+
+    #[test]
+    fn rodeo_test() {
+        let engine = Engine::<RefCell<Rodeo>>::default();
+        let rules = parse::rules(&engine, "($A) dup = ($A) ($A).").unwrap();
+        let terms = rewrite(&engine, &rules, parse::terms(&engine, "(x) dup").unwrap());
+        assert_eq!(pretty::terms(&engine, terms), "(x) (x)");
+        assert_eq!(&*engine.resolve(&engine.get("dup").unwrap()), "dup");
+    }
+
+    #[test]
+    fn frozen_test() {
+        let engine = Engine::new();
+        let rules = parse::rules(&engine, "($A) dup = ($A) ($A).").unwrap();
+        let quote = parse::term(&engine, "(x)").unwrap();
+        let engine = engine.freeze();
+        assert_eq!(parse::term(&engine, "(x)"), Ok(quote));
+        let terms = rewrite(&engine, &rules, parse::terms(&engine, "(x) dup").unwrap());
+        assert_eq!(pretty::terms(&engine, terms), "(x) (x)");
+        assert_eq!(
+            parse::terms(&engine, "(x) y"),
+            Err("Could not intern word \"y\"".to_owned())
+        );
+        assert_eq!(engine.try_get_or_intern("y".to_owned()), None);
+    }
+}
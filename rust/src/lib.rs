@@ -1,104 +1,375 @@
-#![deny(
-    clippy::all,
-    clippy::pedantic,
-    clippy::cargo,
-    clippy::nursery,
-    clippy::cargo
-)]
+#![deny(clippy::all, clippy::pedantic, clippy::cargo, clippy::nursery)]
 
 mod ast;
+pub mod completion;
+pub mod confluence;
+pub mod effect;
+pub mod graph;
+mod interner;
+pub mod linearity;
+pub mod observe;
 pub mod parse;
+pub mod pattern;
 pub mod pretty;
+mod rule_set;
+pub mod shake;
+pub mod stuck;
+mod symbol;
+pub mod termination;
+pub mod transfer;
+pub mod typecheck;
+pub mod validate;
 
 pub use crate::ast::*;
+pub use crate::interner::*;
+use crate::observe::{Cause, Observer, Step};
+pub use crate::rule_set::*;
+pub use crate::symbol::*;
 use im::{vector, Vector};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+/// The order in which `rewrite_with` looks for the next reduction
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub enum Strategy {
+    /// The leftmost start position, then the longest window, then user rules before primitives
+    #[default]
+    LeftmostLongest,
+    /// The leftmost start position, then the shortest window, then user rules before primitives
+    LeftmostShortest,
+    /// The rightmost start position, then the longest window, then user rules before primitives
+    RightmostFirst,
+    /// The leftmost start position, then the longest window, then primitives before user rules
+    PrimitivesFirst,
+}
+
+/// How `rewrite_with` remembers earlier sequences of terms, to notice when it comes back to one
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub enum CycleDetection {
+    /// Earlier sequences of terms are not remembered, so cycles are not noticed
+    #[default]
+    Off,
+    /// Only a hash of each earlier sequence of terms is remembered, which takes little memory,
+    /// but a hash collision can report a cycle that is not there
+    Hashes,
+    /// Each earlier sequence of terms is remembered whole
+    States,
+}
+
+/// The settings `rewrite_with` rewrites under
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Default)]
+pub struct Config {
+    pub strategy: Strategy,
+    /// Whether the contents of quotes are rewritten as well, once nothing outside of them reduces
+    pub inside_quotes: bool,
+    /// The most reductions outside of quotes to perform, or `None` for no limit
+    pub fuel: Option<usize>,
+    /// The most reductions inside of quotes to perform, or `None` for no limit
+    pub quote_fuel: Option<usize>,
+    pub cycle_detection: CycleDetection,
+}
+
+impl Config {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// Why `rewrite_with` stopped rewriting
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Outcome {
+    /// Nothing is left to reduce
+    Normal,
+    /// Something was left to reduce outside of quotes, but the fuel ran out
+    OutOfFuel,
+    /// Something was left to reduce inside of a quote, but the quote fuel ran out
+    OutOfQuoteFuel,
+    /// The sequence of terms came back to what it was `length` reductions earlier, so rewriting
+    /// would go on forever
+    Cycle { length: usize, state: Vector<Term> },
+}
+
+/// The result of `rewrite_with`
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Rewritten {
+    pub terms: Vector<Term>,
+    pub outcome: Outcome,
+    /// The number of reductions performed outside of quotes
+    pub steps: usize,
+    /// The number of reductions performed inside of quotes
+    pub quote_steps: usize,
+}
 
 /// Rewrites a given sequence of terms with the given rules into a new sequence of rules
 #[must_use]
-#[allow(clippy::too_many_lines)]
-pub fn rewrite(engine: &Engine, rules: &Vector<Rule>, terms: Vector<Term>) -> Vector<Term> {
-    let mut start = 0;
-    while start < terms.len() {
+pub fn rewrite<I: Interner>(
+    engine: &Engine<I>,
+    rules: &RuleSet,
+    terms: Vector<Term>,
+) -> Vector<Term> {
+    rewrite_with(engine, rules, terms, &Config::default(), &mut ()).terms
+}
+
+/// Rewrites a given sequence of terms with the given rules under the given settings, telling the observer about every reduction
+pub fn rewrite_with<I: Interner, O: Observer + ?Sized>(
+    engine: &Engine<I>,
+    rules: &RuleSet,
+    mut terms: Vector<Term>,
+    config: &Config,
+    observer: &mut O,
+) -> Rewritten {
+    let (mut steps, mut quote_steps) = (0, 0);
+    let mut seen_hashes = HashMap::new();
+    let mut seen_states = HashMap::new();
+    let mut seen = |terms: &Vector<Term>, index: usize| match config.cycle_detection {
+        CycleDetection::Off => None,
+        CycleDetection::Hashes => {
+            let mut hasher = DefaultHasher::new();
+            terms.hash(&mut hasher);
+            seen_hashes.insert(hasher.finish(), index)
+        }
+        CycleDetection::States => seen_states.insert(terms.clone(), index),
+    };
+    seen(&terms, 0);
+    let outcome = loop {
+        let found = if let Some(found) = find_reduction(engine, rules, &terms, config.strategy) {
+            if config.fuel == Some(steps) {
+                break Outcome::OutOfFuel;
+            }
+            steps += 1;
+            found
+        } else if let Some(found) = config
+            .inside_quotes
+            .then(|| find_quoted_reduction(engine, rules, &terms, config.strategy))
+            .flatten()
+        {
+            if config.quote_fuel == Some(quote_steps) {
+                break Outcome::OutOfQuoteFuel;
+            }
+            quote_steps += 1;
+            found
+        } else {
+            break Outcome::Normal;
+        };
+        let after = replace(engine, &terms, &found);
+        let Found {
+            path,
+            position,
+            length,
+            cause,
+            ..
+        } = found;
+        observer.observe(&Step {
+            before: terms,
+            path,
+            position,
+            length,
+            cause,
+            after: after.clone(),
+        });
+        terms = after;
+        if let Some(earlier) = seen(&terms, steps + quote_steps) {
+            break Outcome::Cycle {
+                length: steps + quote_steps - earlier,
+                state: terms.clone(),
+            };
+        }
+    };
+    Rewritten {
+        terms,
+        outcome,
+        steps,
+        quote_steps,
+    }
+}
+
+/// A reducible window of terms, and what it reduces to
+struct Found {
+    path: Vec<usize>,
+    position: usize,
+    length: usize,
+    cause: Cause,
+    reduction: Vector<Term>,
+}
+
+/// Finds the next reducible window of terms in the order the strategy gives
+fn find_reduction<I: Interner>(
+    engine: &Engine<I>,
+    rules: &RuleSet,
+    terms: &Vector<Term>,
+    strategy: Strategy,
+) -> Option<Found> {
+    let found = |position, length, cause, reduction| {
+        Some(Found {
+            path: vec![],
+            position,
+            length,
+            cause,
+            reduction,
+        })
+    };
+    let starts: Box<dyn Iterator<Item = usize>> = match strategy {
+        Strategy::RightmostFirst => Box::new((0..terms.len()).rev()),
+        _ => Box::new(0..terms.len()),
+    };
+    for start in starts {
         let skipped = terms.skip(start);
-        let mut length = skipped.len();
-        while length > 0 {
-            let pattern = &skipped.take(length);
-            for rule in rules {
-                if &rule.redex == pattern {
-                    return rewrite(
-                        engine,
-                        rules,
-                        terms.take(start) + rule.reduction.clone() + skipped.skip(length),
-                    );
+        let lengths: Box<dyn Iterator<Item = usize>> = match strategy {
+            Strategy::LeftmostShortest => Box::new(1..=skipped.len()),
+            _ => Box::new((1..=skipped.len()).rev()),
+        };
+        for length in lengths {
+            let window = skipped.take(length);
+            if strategy == Strategy::PrimitivesFirst {
+                if let Some((primitive, reduction)) = reduce_primitive(engine, &window) {
+                    return found(start, length, Cause::Primitive(primitive), reduction);
                 }
             }
-            if length == 2 {
-                match skipped[1] {
-                    Term::Prim(Primitive::Unwrap) => {
-                        if let Term::Quote(a) = skipped[0].clone() {
-                            return rewrite(
-                                engine,
-                                rules,
-                                terms.take(start) + a + skipped.skip(length),
-                            );
-                        }
-                    }
-                    Term::Prim(Primitive::Wrap) => {
-                        if skipped[0].is_quote() {
-                            let mut new_terms = terms.take(start);
-                            new_terms.push_back(
-                                Term::make_quote(engine, vector![skipped[0].clone()]).clone(),
-                            );
-                            new_terms.append(skipped.skip(length));
-                            return rewrite(engine, rules, new_terms);
-                        }
-                    }
-                    Term::Prim(Primitive::Discard) => {
-                        if skipped[0].is_quote() {
-                            let new_terms = terms.take(start) + skipped.skip(length);
-                            return rewrite(engine, rules, new_terms);
-                        }
-                    }
-                    Term::Prim(Primitive::Copy) => {
-                        if skipped[0].is_quote() {
-                            let mut new_terms = terms.take(start + 1);
-                            new_terms.push_back(skipped[0].clone());
-                            new_terms.append(skipped.skip(length));
-                            return rewrite(engine, rules, new_terms);
-                        }
-                    }
-                    _ => {}
+            for (id, rule) in rules.candidates(&window) {
+                if let Some(bindings) = pattern::bind(&rule.redex, &window) {
+                    let cause = Cause::Rule(id, rule.clone());
+                    let reduction = pattern::substitute(engine, &rule.reduction, &bindings);
+                    return found(start, length, cause, reduction);
                 }
             }
-            if length == 3 {
-                match skipped[2] {
-                    Term::Prim(Primitive::Combine) => {
-                        if let Term::Quote(a) = skipped[0].clone() {
-                            if let Term::Quote(b) = skipped[1].clone() {
-                                let mut new_terms = terms.take(start);
-                                new_terms.push_back(Term::make_quote(engine, a + b).clone());
-                                new_terms.append(skipped.skip(length));
-                                return rewrite(engine, rules, new_terms);
-                            }
-                        }
-                    }
-                    Term::Prim(Primitive::Swap) => {
-                        if skipped[0].is_quote() && skipped[1].is_quote() {
-                            let mut new_terms = terms.take(start);
-                            new_terms.push_back(skipped[1].clone());
-                            new_terms.push_back(skipped[0].clone());
-                            new_terms.append(skipped.skip(length));
-                            return rewrite(engine, rules, new_terms);
-                        }
-                    }
-                    _ => {}
+            if strategy != Strategy::PrimitivesFirst {
+                if let Some((primitive, reduction)) = reduce_primitive(engine, &window) {
+                    return found(start, length, Cause::Primitive(primitive), reduction);
                 }
             }
-            length -= 1;
         }
-        start += 1;
     }
-    terms
+    None
+}
+
+/// Finds the next reducible window of terms inside of a quote, searching the outermost quotes first
+fn find_quoted_reduction<I: Interner>(
+    engine: &Engine<I>,
+    rules: &RuleSet,
+    terms: &Vector<Term>,
+    strategy: Strategy,
+) -> Option<Found> {
+    let quotes = terms
+        .iter()
+        .enumerate()
+        .filter_map(|(index, term)| match term {
+            Term::Quote(inner) => Some((index, inner)),
+            _ => None,
+        });
+    let quotes: Vec<_> = match strategy {
+        Strategy::RightmostFirst => quotes.rev().collect(),
+        _ => quotes.collect(),
+    };
+    for (index, inner) in &quotes {
+        if let Some(mut found) = find_reduction(engine, rules, inner, strategy) {
+            found.path.insert(0, *index);
+            return Some(found);
+        }
+    }
+    for (index, inner) in quotes {
+        if let Some(mut found) = find_quoted_reduction(engine, rules, inner, strategy) {
+            found.path.insert(0, index);
+            return Some(found);
+        }
+    }
+    None
+}
+
+/// Replaces the window of terms that was found with its reduction
+fn replace<I: Interner>(engine: &Engine<I>, terms: &Vector<Term>, found: &Found) -> Vector<Term> {
+    fn go<I: Interner>(
+        engine: &Engine<I>,
+        terms: &Vector<Term>,
+        path: &[usize],
+        found: &Found,
+    ) -> Vector<Term> {
+        match path.split_first() {
+            None => {
+                terms.take(found.position)
+                    + found.reduction.clone()
+                    + terms.skip(found.position + found.length)
+            }
+            Some((index, path)) => match &terms[*index] {
+                Term::Quote(inner) => {
+                    let inner = go(engine, inner, path, found);
+                    terms.update(*index, Term::make_quote(engine, inner))
+                }
+                _ => unreachable!("the path of a reduction only goes through quotes"),
+            },
+        }
+    }
+    go(engine, terms, &found.path, found)
+}
+
+/// Finds every single reduction that could be performed on a sequence of terms, outside of quotes
+#[must_use]
+pub fn steps<I: Interner>(engine: &Engine<I>, rules: &RuleSet, terms: &Vector<Term>) -> Vec<Step> {
+    let mut steps = Vec::new();
+    for start in 0..terms.len() {
+        let skipped = terms.skip(start);
+        for length in 1..=skipped.len() {
+            let window = skipped.take(length);
+            let mut step = |cause, reduction| {
+                steps.push(Step {
+                    before: terms.clone(),
+                    path: vec![],
+                    position: start,
+                    length,
+                    cause,
+                    after: terms.take(start) + reduction + skipped.skip(length),
+                });
+            };
+            for (id, rule) in rules.candidates(&window) {
+                if let Some(bindings) = pattern::bind(&rule.redex, &window) {
+                    let reduction = pattern::substitute(engine, &rule.reduction, &bindings);
+                    step(Cause::Rule(id, rule.clone()), reduction);
+                }
+            }
+            if let Some((primitive, reduction)) = reduce_primitive(engine, &window) {
+                step(Cause::Primitive(primitive), reduction);
+            }
+        }
+    }
+    steps
+}
+
+/// Reduces a window of terms that is exactly one application of a primitive
+fn reduce_primitive<I: Interner>(
+    engine: &Engine<I>,
+    window: &Vector<Term>,
+) -> Option<(Primitive, Vector<Term>)> {
+    match (window.len(), window.back()) {
+        (2, Some(Term::Prim(primitive))) => {
+            let a = &window[0];
+            let reduction = match (primitive, a) {
+                (Primitive::Unwrap, Term::Quote(inner)) => inner.terms().clone(),
+                (Primitive::Wrap, Term::Quote(_)) => {
+                    vector![Term::make_quote(engine, vector![a.clone()])]
+                }
+                (Primitive::Discard, Term::Quote(_)) => vector![],
+                (Primitive::Copy, Term::Quote(_)) => vector![a.clone(), a.clone()],
+                _ => return None,
+            };
+            Some((*primitive, reduction))
+        }
+        (3, Some(Term::Prim(primitive))) => {
+            let (a, b) = (&window[0], &window[1]);
+            let reduction = match (primitive, a, b) {
+                (Primitive::Combine, Term::Quote(a), Term::Quote(b)) => {
+                    vector![Term::make_quote(
+                        engine,
+                        a.terms().clone() + b.terms().clone()
+                    )]
+                }
+                (Primitive::Swap, Term::Quote(_), Term::Quote(_)) => vector![b.clone(), a.clone()],
+                _ => return None,
+            };
+            Some((*primitive, reduction))
+        }
+        _ => None,
+    }
 }
 
 #[cfg(test)]
@@ -106,9 +377,9 @@ mod tests {
     use super::*;
     use im::vector;
 
-    fn rewrites_to(engine: &Engine, rules: Vector<Rule>, begin: &str, end: &str) {
+    fn rewrites_to(engine: &Engine, rules: impl Into<RuleSet>, begin: &str, end: &str) {
         let begin_terms = parse::terms(engine, begin).unwrap();
-        let rewritten = rewrite(engine, &rules, begin_terms);
+        let rewritten = rewrite(engine, &rules.into(), begin_terms);
         assert_eq!(pretty::terms(engine, rewritten), end.to_owned());
     }
 
@@ -205,6 +476,267 @@ mod tests {
         rewrites_to(&engine, rules.clone(), "a x", "a y z");
         rewrites_to(&engine, rules.clone(), "x a", "y z a");
         rewrites_to(&engine, rules.clone(), "a x b", "a y z b");
-        rewrites_to(&engine, rules.clone(), "x x", "aaaaaaaaa");
+        rewrites_to(&engine, rules, "x x", "aaaaaaaaa");
+    }
+
+    #[test]
+    fn observer_test() {
+        let engine = Engine::new();
+        let rules = parse::rules(&engine, "x = (y) +.").unwrap();
+        let terms = parse::terms(&engine, "x -").unwrap();
+
+        let mut counter = observe::Counter::new();
+        let mut derivation = observe::Derivation::new();
+        let rewritten = rewrite_with(
+            &engine,
+            &rules,
+            terms,
+            &Config::new(),
+            &mut (&mut counter, &mut derivation),
+        );
+        assert_eq!(pretty::terms(&engine, rewritten.terms), "(y)");
+        assert_eq!(
+            (counter.steps, counter.rules, counter.primitives),
+            (3, 1, 2)
+        );
+        let positions: Vec<_> = derivation.steps.iter().map(|step| step.position).collect();
+        assert_eq!(positions, vec![0, 0, 1]);
+        assert_eq!(
+            derivation.steps[1].cause,
+            observe::Cause::Primitive(Primitive::Copy)
+        );
+
+        let mut trace = observe::Trace::new(&engine, Vec::new());
+        let terms = parse::terms(&engine, "(a) (b) ~").unwrap();
+        rewrite_with(&engine, &RuleSet::new(), terms, &Config::new(), &mut trace);
+        assert_eq!(
+            String::from_utf8(trace.into_inner()).unwrap(),
+            "(a) (b) ~ => (b) (a) (by primitive `~` at 0)\n"
+        );
+    }
+
+    #[test]
+    fn strategy_test() {
+        let engine = Engine::new();
+        let rules = parse::rules(&engine, "x = a. x x = b. y = c.").unwrap();
+        let rewrites_under = |strategy, begin, end: &str| {
+            let config = Config {
+                strategy,
+                ..Config::new()
+            };
+            let terms = parse::terms(&engine, begin).unwrap();
+            let rewritten = rewrite_with(&engine, &rules, terms, &config, &mut ());
+            assert_eq!(pretty::terms(&engine, rewritten.terms), end);
+        };
+
+        rewrites_under(Strategy::LeftmostLongest, "x x", "b");
+        rewrites_under(Strategy::LeftmostShortest, "x x", "a a");
+        rewrites_under(Strategy::RightmostFirst, "x x", "a a");
+
+        let mut derivation = observe::Derivation::new();
+        let config = Config {
+            strategy: Strategy::RightmostFirst,
+            ..Config::new()
+        };
+        let terms = parse::terms(&engine, "x y").unwrap();
+        rewrite_with(&engine, &rules, terms, &config, &mut derivation);
+        assert_eq!(derivation.steps[0].position, 1);
+
+        let terms = parse::terms(&engine, "(a) +").unwrap();
+        let rules = RuleSet::from(vector![Rule {
+            redex: terms.clone(),
+            reduction: parse::terms(&engine, "b").unwrap(),
+        }]);
+        let rewritten = rewrite_with(&engine, &rules, terms.clone(), &Config::new(), &mut ());
+        assert_eq!(pretty::terms(&engine, rewritten.terms), "b");
+        let config = Config {
+            strategy: Strategy::PrimitivesFirst,
+            ..Config::new()
+        };
+        let rewritten = rewrite_with(&engine, &rules, terms, &config, &mut ());
+        assert_eq!(pretty::terms(&engine, rewritten.terms), "(a) (a)");
+    }
+
+    #[test]
+    fn inside_quotes_test() {
+        let engine = Engine::new();
+        let rules = parse::rules(&engine, "x = y. z = z.").unwrap();
+        let normalizes_to = |config: &Config, begin, end: &str| {
+            let terms = parse::terms(&engine, begin).unwrap();
+            let rewritten = rewrite_with(&engine, &rules, terms, config, &mut ());
+            assert_eq!(pretty::terms(&engine, rewritten.terms.clone()), end);
+            rewritten
+        };
+
+        let config = Config {
+            inside_quotes: true,
+            ..Config::new()
+        };
+        rewrites_to(&engine, rules.clone(), "(x)", "(x)");
+        let rewritten = normalizes_to(&config, "(x) (a ((b) (x) ~))", "(y) (a ((y) (b)))");
+        assert_eq!(rewritten.outcome, Outcome::Normal);
+        assert_eq!((rewritten.steps, rewritten.quote_steps), (0, 3));
+        let rewritten = normalizes_to(&config, "(x) <", "y");
+        assert_eq!((rewritten.steps, rewritten.quote_steps), (2, 0));
+
+        let config = Config {
+            inside_quotes: true,
+            quote_fuel: Some(1),
+            ..Config::new()
+        };
+        let rewritten = normalizes_to(&config, "(x) (x)", "(y) (x)");
+        assert_eq!(rewritten.outcome, Outcome::OutOfQuoteFuel);
+
+        let config = Config {
+            inside_quotes: true,
+            fuel: Some(5),
+            ..Config::new()
+        };
+        let rewritten = normalizes_to(&config, "(x) z", "(x) z");
+        assert_eq!(rewritten.outcome, Outcome::OutOfFuel);
+        assert_eq!((rewritten.steps, rewritten.quote_steps), (5, 0));
+
+        let mut derivation = observe::Derivation::new();
+        let terms = parse::terms(&engine, "a ((x))").unwrap();
+        rewrite_with(&engine, &rules, terms, &config, &mut derivation);
+        assert_eq!(derivation.steps[0].path, vec![1, 0]);
+        assert_eq!(derivation.steps[0].position, 0);
+    }
+
+    #[test]
+    fn variable_test() {
+        let engine = Engine::new();
+        let rules = parse::rules(
+            &engine,
+            "($A) ($B) dip2 = $B ($A). $A dup = $A $A. $A $A same = yes. ($A) twice = $A $A. $A > quoted = ($A).",
+        )
+        .unwrap();
+
+        rewrites_to(&engine, rules.clone(), "(x) (y z) dip2", "y z (x)");
+        rewrites_to(&engine, rules.clone(), "(x) (y) dip2", "y (x)");
+        rewrites_to(&engine, rules.clone(), "x (y) dip2", "x (y) dip2");
+        rewrites_to(&engine, rules.clone(), "(x (y)) dup", "(x (y)) (x (y))");
+        rewrites_to(&engine, rules.clone(), "x dup", "x dup");
+        rewrites_to(&engine, rules.clone(), "(x) (x) same", "yes");
+        rewrites_to(&engine, rules.clone(), "(x) (y) same", "(x) (y) same");
+        rewrites_to(&engine, rules.clone(), "(a) twice", "a a");
+        rewrites_to(&engine, rules, "(a) > quoted", "((a))");
+
+        assert!(parse::rule(&engine, "$A = $B.").is_err());
+        assert!(parse::rule(&engine, "$A ($A) foo = x.").is_err());
+
+        // Words that are not marked as variables keep meaning words, whatever their case
+        let rule = parse::rule(&engine, "x = Foo.").unwrap();
+        assert_eq!(pretty::rule(&engine, rule), "x = Foo.");
+        let rules = parse::rules(&engine, "Foo = x.").unwrap();
+        rewrites_to(&engine, rules.clone(), "Foo", "x");
+        rewrites_to(&engine, rules, "(y)", "(y)");
+    }
+
+    #[test]
+    fn literal_redex_test() {
+        let engine = Engine::new();
+        let rules = parse::rules(
+            &engine,
+            "(x) foo = bar. foo + = baz. (x) + = twice. (x $A) first = ($A). (($A) $B) nested = $A ($B).",
+        )
+        .unwrap();
+
+        rewrites_to(&engine, rules.clone(), "(x) foo", "bar");
+        rewrites_to(&engine, rules.clone(), "(y) foo", "(y) foo");
+        rewrites_to(&engine, rules.clone(), "foo +", "baz");
+        rewrites_to(&engine, rules.clone(), "(x) +", "twice");
+        rewrites_to(&engine, rules.clone(), "(y) +", "(y) (y)");
+        rewrites_to(&engine, rules.clone(), "(x y z) first", "(y z)");
+        rewrites_to(&engine, rules.clone(), "(x) first", "()");
+        rewrites_to(&engine, rules.clone(), "(y x) first", "(y x) first");
+        rewrites_to(&engine, rules, "((a b) c d) nested", "a b (c d)");
+    }
+
+    #[test]
+    fn cycle_test() {
+        let engine = Engine::new();
+        let rules = parse::rules(&engine, "a = b. b = a. c = d c.").unwrap();
+        let rewrites_under = |cycle_detection, begin| {
+            let config = Config {
+                cycle_detection,
+                fuel: Some(20),
+                ..Config::new()
+            };
+            let terms = parse::terms(&engine, begin).unwrap();
+            rewrite_with(&engine, &rules, terms, &config, &mut ())
+        };
+
+        for cycle_detection in [CycleDetection::Hashes, CycleDetection::States] {
+            let rewritten = rewrites_under(cycle_detection, "x a");
+            assert_eq!(
+                rewritten.outcome,
+                Outcome::Cycle {
+                    length: 2,
+                    state: parse::terms(&engine, "x a").unwrap()
+                }
+            );
+            assert_eq!(rewritten.steps, 2);
+
+            let rewritten = rewrites_under(cycle_detection, "(+ <) + <");
+            assert!(matches!(
+                rewritten.outcome,
+                Outcome::Cycle { length: 2, .. }
+            ));
+
+            let rewritten = rewrites_under(cycle_detection, "c");
+            assert_eq!(rewritten.outcome, Outcome::OutOfFuel);
+        }
+        let rewritten = rewrites_under(CycleDetection::Off, "a");
+        assert_eq!(rewritten.outcome, Outcome::OutOfFuel);
+
+        // The quotes of the cycle are dropped and made again with new ids along the way
+        let rules = parse::rules(&engine, "($A) a = ($A) (z) , b. ($A z) b = ($A) a.").unwrap();
+        for cycle_detection in [CycleDetection::Hashes, CycleDetection::States] {
+            let config = Config {
+                cycle_detection,
+                fuel: Some(20),
+                ..Config::new()
+            };
+            let terms = parse::terms(&engine, "(x) a").unwrap();
+            let rewritten = rewrite_with(&engine, &rules, terms, &config, &mut ());
+            assert!(matches!(
+                rewritten.outcome,
+                Outcome::Cycle { length: 3, .. }
+            ));
+        }
+    }
+
+    #[test]
+    fn threads_test() {
+        let engine = Engine::new();
+        let rules = parse::rules(&engine, "dup = +. ($A) twice = ($A) ($A) ,. one = (x).").unwrap();
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..8)
+                .map(|thread| {
+                    let (engine, rules) = (&engine, &rules);
+                    scope.spawn(move || {
+                        let word = format!("w{thread}");
+                        let input = format!("({word}) twice dup one twice");
+                        for _ in 0..50 {
+                            let terms = parse::terms(engine, &input).unwrap();
+                            let rewritten = rewrite(engine, rules, terms);
+                            let expected = format!("({word} {word}) ({word} {word}) (x x)");
+                            assert_eq!(pretty::terms(engine, rewritten), expected);
+                        }
+                        parse::term(engine, &format!("({word} {word})")).unwrap()
+                    })
+                })
+                .collect();
+            let quotes: Vec<_> = handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect();
+            for (thread, quote) in quotes.into_iter().enumerate() {
+                let input = format!("(w{thread}) twice");
+                let terms = rewrite(&engine, &rules, parse::terms(&engine, &input).unwrap());
+                assert_eq!(terms, vector![quote]);
+            }
+        });
     }
 }
@@ -0,0 +1,289 @@
+use crate::{graph, pattern, Primitive, Rule, RuleSet, Term};
+use im::{HashMap, HashSet, Vector};
+use lasso::Spur;
+use std::fmt;
+
+/// Whether running some terms can copy or drop quotes
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Default)]
+pub struct Usage {
+    pub copies: bool,
+    pub drops: bool,
+}
+
+impl Usage {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            copies: false,
+            drops: false,
+        }
+    }
+
+    /// Quotes are used exactly once
+    #[must_use]
+    pub const fn is_linear(self) -> bool {
+        !self.copies && !self.drops
+    }
+
+    /// Quotes are used at most once
+    #[must_use]
+    pub const fn is_affine(self) -> bool {
+        !self.copies
+    }
+
+    #[must_use]
+    pub const fn union(self, other: Self) -> Self {
+        Self {
+            copies: self.copies || other.copies,
+            drops: self.drops || other.drops,
+        }
+    }
+}
+
+impl fmt::Display for Usage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.copies, self.drops) {
+            (false, false) => write!(f, "uses every quote once"),
+            (false, true) => write!(f, "drops quotes"),
+            (true, false) => write!(f, "copies quotes"),
+            (true, true) => write!(f, "copies and drops quotes"),
+        }
+    }
+}
+
+/// How a set of rules may use quotes
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Discipline {
+    /// Quotes can be dropped but not copied
+    Affine,
+    /// Quotes can be neither copied nor dropped
+    Linear,
+}
+
+impl Discipline {
+    #[must_use]
+    pub const fn allows(self, usage: Usage) -> bool {
+        match self {
+            Self::Affine => usage.is_affine(),
+            Self::Linear => usage.is_linear(),
+        }
+    }
+}
+
+/// A rule that uses quotes in a way its discipline does not allow
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct Violation {
+    pub rule: usize,
+    pub discipline: Discipline,
+    pub usage: Usage,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let discipline = match self.discipline {
+            Discipline::Affine => "affine",
+            Discipline::Linear => "linear",
+        };
+        write!(
+            f,
+            "rule {} is not {discipline} because it {}",
+            self.rule, self.usage
+        )
+    }
+}
+
+/// How the primitives in some terms use quotes, including inside of quotes that may be unwrapped
+fn primitives(terms: &Vector<Term>) -> Usage {
+    terms.iter().fold(Usage::new(), |usage, term| {
+        usage.union(match term {
+            Term::Prim(Primitive::Copy) => Usage {
+                copies: true,
+                drops: false,
+            },
+            Term::Prim(Primitive::Discard) => Usage {
+                copies: false,
+                drops: true,
+            },
+            Term::Quote(inner) => primitives(inner),
+            _ => Usage::new(),
+        })
+    })
+}
+
+/// How many times a quote occurs in some terms, including inside of other quotes
+fn quotes(terms: &Vector<Term>, quote: &Term) -> usize {
+    terms
+        .iter()
+        .map(|term| match term {
+            _ if term == quote => 1,
+            Term::Quote(inner) => quotes(inner, quote),
+            _ => 0,
+        })
+        .sum()
+}
+
+/// How a rule uses quotes by itself: a variable or a quote without variables that occurs more
+/// often in the reduction than in the redex is copied, one that occurs less often is dropped, and
+/// the primitives in the reduction copy and drop what they take
+fn local(rule: &Rule) -> Usage {
+    let variables = pattern::variables(&rule.redex).into_iter().map(|var| {
+        (
+            pattern::occurrences(&rule.redex, var),
+            pattern::occurrences(&rule.reduction, var),
+        )
+    });
+    let literals = rule
+        .redex
+        .iter()
+        .filter(|term| matches!(term, Term::Quote(inner) if pattern::variables(inner).is_empty()))
+        .map(|quote| (quotes(&rule.redex, quote), quotes(&rule.reduction, quote)));
+    variables
+        .chain(literals)
+        .fold(primitives(&rule.reduction), |usage, (before, after)| {
+            usage.union(Usage {
+                copies: after > before,
+                drops: after < before,
+            })
+        })
+}
+
+/// How the rules that can fire because of running some words use quotes
+fn reachable(
+    rules: &RuleSet,
+    dependencies: &HashMap<Spur, HashSet<Spur>>,
+    words: impl IntoIterator<Item = Spur>,
+) -> Usage {
+    let mut stack: Vec<_> = words.into_iter().collect();
+    let mut seen: HashSet<_> = stack.iter().copied().collect();
+    let mut usage = Usage::new();
+    while let Some(word) = stack.pop() {
+        for (_, rule) in rules.by_head(word) {
+            usage = usage.union(local(rule));
+        }
+        for next in dependencies.get(&word).into_iter().flatten() {
+            if seen.insert(*next).is_none() {
+                stack.push(*next);
+            }
+        }
+    }
+    usage
+}
+
+/// Finds how running a word can use quotes, from every rule that can fire because of it
+#[must_use]
+pub fn word(rules: &RuleSet, word: Spur) -> Usage {
+    reachable(rules, &graph::rules(rules).dependencies, [word])
+}
+
+/// Finds how a rule uses quotes, including the rules of the words in its reduction
+#[must_use]
+pub fn rule(rules: &RuleSet, rule: &Rule) -> Usage {
+    let mut words = HashSet::new();
+    graph::words(&rule.reduction, &mut words);
+    let dependencies = graph::rules(rules).dependencies;
+    local(rule).union(reachable(rules, &dependencies, words))
+}
+
+/// Finds how every word that a rule defines uses quotes
+#[must_use]
+pub fn words(rules: &RuleSet) -> HashMap<Spur, Usage> {
+    let dependencies = graph::rules(rules).dependencies;
+    rules
+        .iter()
+        .filter_map(|(_, rule)| rule.head())
+        .map(|head| (head, reachable(rules, &dependencies, [head])))
+        .collect()
+}
+
+/// Finds the rules that use quotes in a way the discipline does not allow, including through the
+/// rules of the words in their reductions
+#[must_use]
+pub fn check(rules: &RuleSet, discipline: Discipline) -> Vec<Violation> {
+    rules
+        .iter()
+        .filter_map(|(id, r)| {
+            let usage = rule(rules, r);
+            (!discipline.allows(usage)).then_some(Violation {
+                rule: id,
+                discipline,
+                usage,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse, Engine};
+
+    #[test]
+    fn usage_test() {
+        let engine = Engine::new();
+        let rules = parse::rules(
+            &engine,
+            "($A) ($B) swap = ($B) ($A). ($A) dup = ($A) ($A). ($A) drop = . \
+             ($A) twice = ($A) dup ,. ($A) ($B) k = ($A) drop. ($A) id = ($A) swap swap.",
+        )
+        .unwrap();
+        let usage = |name| word(&rules, engine.get(name).unwrap());
+        let copies = Usage {
+            copies: true,
+            drops: false,
+        };
+        let drops = Usage {
+            copies: false,
+            drops: true,
+        };
+        assert_eq!(usage("swap"), Usage::new());
+        assert_eq!(usage("dup"), copies);
+        assert_eq!(usage("drop"), drops);
+        assert_eq!(usage("twice"), copies);
+        assert_eq!(usage("k"), drops);
+        assert!(usage("id").is_linear());
+        assert_eq!(words(&rules).len(), 6);
+
+        let r = parse::rule(&engine, "($A) f = ($A) + twice.").unwrap();
+        assert_eq!(rule(&rules, &r), copies);
+        let r = parse::rule(&engine, "($A) ($B) f = ($B) (-) <.").unwrap();
+        assert_eq!(rule(&rules, &r).to_string(), "drops quotes");
+
+        let r = parse::rule(&engine, "(x) foo = bar.").unwrap();
+        assert_eq!(rule(&rules, &r), drops);
+        let r = parse::rule(&engine, "(x) (x) foo = ((x)) bar (x).").unwrap();
+        assert!(rule(&rules, &r).is_linear());
+        let r = parse::rule(&engine, "(x) foo = (x) (x).").unwrap();
+        assert_eq!(rule(&rules, &r), copies);
+    }
+
+    #[test]
+    fn check_test() {
+        let engine = Engine::new();
+        let rules = parse::rules(
+            &engine,
+            "($A) ($B) swap = ($B) ($A). ($A) dup = ($A) ($A). ($A) drop = -. x = (y) swap. \
+             (x) foo = bar.",
+        )
+        .unwrap();
+        assert_eq!(
+            check(&rules, Discipline::Affine),
+            vec![Violation {
+                rule: 1,
+                discipline: Discipline::Affine,
+                usage: Usage {
+                    copies: true,
+                    drops: false,
+                },
+            }]
+        );
+        let violations = check(&rules, Discipline::Linear);
+        assert_eq!(
+            violations.iter().map(|v| v.rule).collect::<Vec<_>>(),
+            vec![1, 2, 4]
+        );
+        assert_eq!(
+            violations[1].to_string(),
+            "rule 2 is not linear because it drops quotes"
+        );
+    }
+}
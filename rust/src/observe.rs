@@ -0,0 +1,143 @@
+use crate::{pretty, Engine, Interner, Primitive, Rule, Term};
+use im::Vector;
+use lasso::ThreadedRodeo;
+use std::io::Write;
+
+/// What caused a single reduction
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Cause {
+    /// The user rule with the given id in its set fired
+    Rule(usize, Rule),
+    /// The built-in reduction of a primitive fired
+    Primitive(Primitive),
+}
+
+/// A single reduction performed while rewriting
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Step {
+    /// The whole sequence of terms before the reduction
+    pub before: Vector<Term>,
+    /// The indices of the quotes the reduction happened inside of, from the outermost in
+    pub path: Vec<usize>,
+    /// The index of the first reduced term, inside of the innermost quote if there is one
+    pub position: usize,
+    /// The number of reduced terms
+    pub length: usize,
+    /// The rule or primitive that was reduced
+    pub cause: Cause,
+    /// The whole sequence of terms after the reduction
+    pub after: Vector<Term>,
+}
+
+/// Something that is told about every reduction `rewrite_with` performs
+pub trait Observer {
+    /// Called once for every reduction, in the order they happen
+    fn observe(&mut self, step: &Step);
+}
+
+impl Observer for () {
+    fn observe(&mut self, _step: &Step) {}
+}
+
+impl<O: Observer + ?Sized> Observer for &mut O {
+    fn observe(&mut self, step: &Step) {
+        (**self).observe(step);
+    }
+}
+
+impl<A: Observer, B: Observer> Observer for (A, B) {
+    fn observe(&mut self, step: &Step) {
+        self.0.observe(step);
+        self.1.observe(step);
+    }
+}
+
+/// An observer that prints every reduction to a writer
+pub struct Trace<'e, W, I = ThreadedRodeo> {
+    engine: &'e Engine<I>,
+    writer: W,
+}
+
+impl<'e, W: Write, I: Interner> Trace<'e, W, I> {
+    #[must_use]
+    pub const fn new(engine: &'e Engine<I>, writer: W) -> Self {
+        Self { engine, writer }
+    }
+
+    /// Gives back the writer the trace was printed to
+    #[must_use]
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write, I: Interner> Observer for Trace<'_, W, I> {
+    fn observe(&mut self, step: &Step) {
+        let cause = match &step.cause {
+            Cause::Rule(id, rule) => {
+                format!("rule id {id} `{}`", pretty::rule(self.engine, rule.clone()))
+            }
+            Cause::Primitive(primitive) => format!("primitive `{primitive}`"),
+        };
+        // A trace is a debugging aid, so a failing writer should not stop the rewrite
+        let _ = writeln!(
+            self.writer,
+            "{} => {} (by {cause} at {})",
+            pretty::terms(self.engine, step.before.clone()),
+            pretty::terms(self.engine, step.after.clone()),
+            step.path
+                .iter()
+                .chain([&step.position])
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(".")
+        );
+    }
+}
+
+/// An observer that counts reductions
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub struct Counter {
+    /// The number of reductions of any kind
+    pub steps: usize,
+    /// The number of reductions by user rules
+    pub rules: usize,
+    /// The number of reductions by primitives
+    pub primitives: usize,
+}
+
+impl Counter {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Observer for Counter {
+    fn observe(&mut self, step: &Step) {
+        self.steps += 1;
+        match step.cause {
+            Cause::Rule(..) => self.rules += 1,
+            Cause::Primitive(_) => self.primitives += 1,
+        }
+    }
+}
+
+/// An observer that collects every reduction into a full derivation
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Default)]
+pub struct Derivation {
+    pub steps: Vec<Step>,
+}
+
+impl Derivation {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Observer for Derivation {
+    fn observe(&mut self, step: &Step) {
+        self.steps.push(step.clone());
+    }
+}
@@ -1,6 +1,10 @@
 use crate::ast::{Engine, Primitive, Rule, Term};
+use crate::RuleSet;
+use crate::{Interner, Location, Span};
 use im::Vector;
+use lasso::Spur;
 pub use lasso::ThreadedRodeo;
+use std::collections::HashMap;
 use std::iter::{FusedIterator, Iterator};
 use std::str::Chars;
 
@@ -22,150 +26,111 @@ enum ParseError {
 
 struct Tokens<'iter> {
     chars: Chars<'iter>,
-    peeked: Vec<Token>,
+    peeked: Vec<(Token, Span)>,
+    /// How many bytes of the input the characters read so far take up
+    offset: usize,
+    /// Where the last token that was taken out of the tokens ends
+    end: usize,
 }
 
 impl<'iter> Tokens<'iter> {
-    fn iter_next(&mut self) -> Vec<Token> {
+    fn iter_next(&mut self) -> Vec<(Token, Span)> {
         let mut buf = String::new();
+        let mut start = self.offset;
         loop {
-            if let Some(c) = self.chars.next() {
-                if c.is_whitespace() {
-                    if !buf.is_empty() {
-                        return vec![Token::Word(buf)];
-                    }
-                    continue;
+            let here = self.offset;
+            let Some(c) = self.chars.next() else {
+                let mut tokens = vec![];
+                if !buf.is_empty() {
+                    tokens.push((Token::Word(buf), Span::new(start, here)));
                 }
-                match c {
-                    '=' => {
-                        return if buf.is_empty() {
-                            vec![Token::Equals]
-                        } else {
-                            vec![Token::Word(buf), Token::Equals]
-                        }
-                    }
-                    '.' => {
-                        return if buf.is_empty() {
-                            vec![Token::Period]
-                        } else {
-                            vec![Token::Word(buf), Token::Period]
-                        }
-                    }
-                    '~' => {
-                        return if buf.is_empty() {
-                            vec![Token::Prim(Primitive::Swap)]
-                        } else {
-                            vec![Token::Word(buf), Token::Prim(Primitive::Swap)]
-                        }
-                    }
-                    ',' => {
-                        return if buf.is_empty() {
-                            vec![Token::Prim(Primitive::Combine)]
-                        } else {
-                            vec![Token::Word(buf), Token::Prim(Primitive::Combine)]
-                        }
-                    }
-                    '+' => {
-                        return if buf.is_empty() {
-                            vec![Token::Prim(Primitive::Copy)]
-                        } else {
-                            vec![Token::Word(buf), Token::Prim(Primitive::Copy)]
-                        }
-                    }
-                    '-' => {
-                        return if buf.is_empty() {
-                            vec![Token::Prim(Primitive::Discard)]
-                        } else {
-                            vec![Token::Word(buf), Token::Prim(Primitive::Discard)]
-                        }
-                    }
-                    '>' => {
-                        return if buf.is_empty() {
-                            vec![Token::Prim(Primitive::Wrap)]
-                        } else {
-                            vec![Token::Word(buf), Token::Prim(Primitive::Wrap)]
-                        }
-                    }
-                    '<' => {
-                        return if buf.is_empty() {
-                            vec![Token::Prim(Primitive::Unwrap)]
-                        } else {
-                            vec![Token::Word(buf), Token::Prim(Primitive::Unwrap)]
-                        }
-                    }
-                    '(' => {
-                        return if buf.is_empty() {
-                            vec![Token::LeftParen]
-                        } else {
-                            vec![Token::Word(buf), Token::LeftParen]
-                        }
-                    }
-                    ')' => {
-                        return if buf.is_empty() {
-                            vec![Token::RightParen]
-                        } else {
-                            vec![Token::Word(buf), Token::RightParen]
-                        }
-                    }
-                    _ => {
-                        buf.push(c);
+                tokens.push((Token::EndOfInput, Span::new(here, here)));
+                return tokens;
+            };
+            self.offset += c.len_utf8();
+            if c.is_whitespace() {
+                if !buf.is_empty() {
+                    return vec![(Token::Word(buf), Span::new(start, here))];
+                }
+                continue;
+            }
+            let token = match c {
+                '=' => Token::Equals,
+                '.' => Token::Period,
+                '~' => Token::Prim(Primitive::Swap),
+                ',' => Token::Prim(Primitive::Combine),
+                '+' => Token::Prim(Primitive::Copy),
+                '-' => Token::Prim(Primitive::Discard),
+                '>' => Token::Prim(Primitive::Wrap),
+                '<' => Token::Prim(Primitive::Unwrap),
+                '(' => Token::LeftParen,
+                ')' => Token::RightParen,
+                _ => {
+                    if buf.is_empty() {
+                        start = here;
                     }
+                    buf.push(c);
+                    continue;
                 }
-            } else {
-                return if buf.is_empty() {
-                    vec![Token::EndOfInput]
-                } else {
-                    vec![Token::Word(buf), Token::EndOfInput]
-                };
+            };
+            let mut tokens = vec![];
+            if !buf.is_empty() {
+                tokens.push((Token::Word(buf), Span::new(start, here)));
             }
+            tokens.push((token, Span::new(here, self.offset)));
+            return tokens;
         }
     }
 
-    fn peek(&mut self) -> Token {
+    fn fill(&mut self) {
         if self.peeked.is_empty() {
             let next_tokens = self.iter_next();
             self.peeked.extend(next_tokens);
         }
-        self.peeked[0].clone()
+    }
+
+    fn peek(&mut self) -> Token {
+        self.fill();
+        self.peeked[0].0.clone()
+    }
+
+    /// Where the next token starts
+    fn start(&mut self) -> usize {
+        self.fill();
+        self.peeked[0].1.start
     }
 
     fn at_eoi(&mut self) -> bool {
-        if self.peeked.is_empty() {
-            let next_tokens = self.iter_next();
-            self.peeked.extend(next_tokens);
-        }
-        self.peeked[0] == Token::EndOfInput
+        self.fill();
+        self.peeked[0].0 == Token::EndOfInput
     }
 
     fn advance(&mut self) {
-        if self.peeked.is_empty() {
-            let next_tokens = self.iter_next();
-            self.peeked.extend(next_tokens);
-        }
-        self.peeked.remove(0);
+        let _ = self.next();
     }
 
     fn new(s: &'iter str) -> Self {
         Tokens {
             chars: s.chars(),
             peeked: vec![],
+            offset: 0,
+            end: 0,
         }
     }
 }
 
-impl<'iter> Iterator for Tokens<'iter> {
+impl Iterator for Tokens<'_> {
     type Item = Token;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.peeked.is_empty() {
-            let next_tokens = self.iter_next();
-            self.peeked.extend(next_tokens);
-        }
-        Some(self.peeked.remove(0))
+        self.fill();
+        let (token, span) = self.peeked.remove(0);
+        self.end = span.end;
+        Some(token)
     }
 }
 
-impl<'iter> FusedIterator for Tokens<'iter> {}
+impl FusedIterator for Tokens<'_> {}
 
 fn display_token(token: &Token) -> String {
     match token {
@@ -174,16 +139,23 @@ fn display_token(token: &Token) -> String {
         Token::Prim(primitive) => primitive.to_string(),
         Token::Period => ".".to_string(),
         Token::Equals => "=".to_string(),
-        Token::Word(s) => format!("word \"{}\"", s),
+        Token::Word(s) => format!("word \"{s}\""),
         Token::EndOfInput => "end of input".to_string(),
     }
 }
 
-fn parse_term<'a>(engine: &'a Engine, tokens: &mut Tokens) -> Result<&'a mut Term, ParseError> {
+/// Interns a word that was just read, which fails if the engine can not take new words
+fn intern<I: Interner>(engine: &Engine<I>, s: &str) -> Result<Spur, ParseError> {
+    engine
+        .try_get_or_intern(s.to_owned())
+        .ok_or_else(|| ParseError::Consumed(format!("Could not intern word \"{s}\"")))
+}
+
+fn parse_term<I: Interner>(engine: &Engine<I>, tokens: &mut Tokens) -> Result<Term, ParseError> {
     match tokens.peek() {
         Token::Word(s) => {
             tokens.advance();
-            Ok(Term::make_word(engine, engine.get_or_intern(s)))
+            Ok(Term::make_word(engine, intern(engine, &s)?))
         }
         Token::Prim(primitive) => {
             tokens.advance();
@@ -208,31 +180,67 @@ fn parse_term<'a>(engine: &'a Engine, tokens: &mut Tokens) -> Result<&'a mut Ter
     }
 }
 
-fn parse_nonquote_term<'a>(
-    engine: &'a Engine,
+/// Whether a word in a rule names a variable, which it does if it starts with `$`
+fn is_variable(s: &str) -> bool {
+    s.len() > 1 && s.starts_with('$')
+}
+
+/// Whether a string is read as a single word, which also does not start like a variable
+pub(crate) fn is_word(s: &str) -> bool {
+    !s.is_empty()
+        && !s.starts_with('$')
+        && !s
+            .chars()
+            .any(|c| c.is_whitespace() || "=.~,+-><()".contains(c))
+}
+
+fn parse_redex_term<I: Interner>(
+    engine: &Engine<I>,
     tokens: &mut Tokens,
-) -> Result<&'a mut Term, ParseError> {
+    quoted: bool,
+) -> Result<Term, ParseError> {
     match tokens.peek() {
         Token::Word(s) => {
             tokens.advance();
-            Ok(Term::make_word(engine, engine.get_or_intern(s)))
+            let variable = is_variable(&s);
+            let spur = intern(engine, &s)?;
+            Ok(match (variable, quoted) {
+                (true, true) => Term::make_seq_var(engine, spur),
+                (true, false) => Term::make_var(engine, spur),
+                (false, _) => Term::make_word(engine, spur),
+            })
         }
         Token::Prim(primitive) => {
             tokens.advance();
             Ok(Term::make_prim(engine, primitive))
         }
+        Token::LeftParen => {
+            tokens.advance();
+            let terms = parse_redex_terms(engine, tokens, true).map_err(ParseError::Consumed)?;
+            match tokens.next() {
+                Some(Token::RightParen) => Ok(Term::make_quote(engine, terms)),
+                Some(token) => Err(ParseError::Consumed(format!(
+                    "Expected ')' but found {}",
+                    display_token(&token)
+                ))),
+                None => panic!("inconceivable"),
+            }
+        }
         token => Err(ParseError::DidNotConsume(format!(
-            "Expected non-quote term but found {}",
+            "Expected redex term but found {}",
             display_token(&token)
         ))),
     }
 }
 
-fn parse_terms(engine: &Engine, tokens: &mut Tokens) -> Result<Vector<Term>, String> {
+fn parse_terms<I: Interner>(
+    engine: &Engine<I>,
+    tokens: &mut Tokens,
+) -> Result<Vector<Term>, String> {
     let mut terms = Vector::new();
     loop {
         match parse_term(engine, tokens) {
-            Ok(term) => terms.push_back(term.clone()),
+            Ok(term) => terms.push_back(term),
             Err(ParseError::Consumed(err)) => return Err(err),
             Err(ParseError::DidNotConsume(_)) => break,
         }
@@ -241,11 +249,15 @@ fn parse_terms(engine: &Engine, tokens: &mut Tokens) -> Result<Vector<Term>, Str
     Ok(terms)
 }
 
-fn parse_nonquote_terms(engine: &Engine, tokens: &mut Tokens) -> Result<Vector<Term>, String> {
+fn parse_redex_terms<I: Interner>(
+    engine: &Engine<I>,
+    tokens: &mut Tokens,
+    quoted: bool,
+) -> Result<Vector<Term>, String> {
     let mut terms = Vector::new();
     loop {
-        match parse_nonquote_term(engine, tokens) {
-            Ok(term) => terms.push_back(term.clone()),
+        match parse_redex_term(engine, tokens, quoted) {
+            Ok(term) => terms.push_back(term),
             Err(ParseError::Consumed(err)) => return Err(err),
             Err(ParseError::DidNotConsume(_)) => break,
         }
@@ -254,14 +266,42 @@ fn parse_nonquote_terms(engine: &Engine, tokens: &mut Tokens) -> Result<Vector<T
     Ok(terms)
 }
 
-fn parse_rule(engine: &Engine, tokens: &mut Tokens) -> Result<Rule, ParseError> {
-    let redex = parse_nonquote_terms(engine, tokens).map_err(ParseError::Consumed)?;
+/// Parses a rule, along with where it is and where the word it defines is
+fn parse_rule<I: Interner>(
+    engine: &Engine<I>,
+    tokens: &mut Tokens,
+) -> Result<(Rule, Span, Option<Span>), ParseError> {
+    let start = tokens.start();
+    let mut redex = Vector::new();
+    let mut spans = Vec::new();
+    loop {
+        let term_start = tokens.start();
+        match parse_redex_term(engine, tokens, false) {
+            Ok(term) => {
+                redex.push_back(term);
+                spans.push(Span::new(term_start, tokens.end));
+            }
+            Err(ParseError::Consumed(err)) => return Err(ParseError::Consumed(err)),
+            Err(ParseError::DidNotConsume(_)) => break,
+        }
+    }
+    let head = redex
+        .iter()
+        .rposition(|term| matches!(term, Term::Word(_)))
+        .map(|index| spans[index]);
     match tokens.peek() {
         Token::Equals => {
             tokens.advance();
+            let vars = redex_variables(engine, &redex).map_err(ParseError::Consumed)?;
             let reduction = parse_terms(engine, tokens).map_err(ParseError::Consumed)?;
+            let reduction =
+                bind_variables(engine, &vars, reduction).map_err(ParseError::Consumed)?;
             match tokens.next() {
-                Some(Token::Period) => Ok(Rule { redex, reduction }),
+                Some(Token::Period) => Ok((
+                    Rule { redex, reduction },
+                    Span::new(start, tokens.end),
+                    head,
+                )),
                 Some(token) => Err(ParseError::Consumed(format!(
                     "Expected ';' but found {}",
                     display_token(&token)
@@ -280,12 +320,77 @@ fn parse_rule(engine: &Engine, tokens: &mut Tokens) -> Result<Rule, ParseError>
     }
 }
 
-fn parse_rules(engine: &Engine, tokens: &mut Tokens) -> Result<Vector<Rule>, ParseError> {
-    let mut rules = Vector::new();
+/// Finds the variables of a redex, and whether they stand for the contents of a quote
+fn redex_variables<I: Interner>(
+    engine: &Engine<I>,
+    redex: &Vector<Term>,
+) -> Result<HashMap<Spur, bool>, String> {
+    fn go<I: Interner>(
+        engine: &Engine<I>,
+        terms: &Vector<Term>,
+        vars: &mut HashMap<Spur, bool>,
+    ) -> Result<(), String> {
+        for term in terms {
+            let (var, is_seq) = match term {
+                Term::Var(var) => (*var, false),
+                Term::SeqVar(var) => (*var, true),
+                Term::Quote(inner) => {
+                    go(engine, inner, vars)?;
+                    continue;
+                }
+                _ => continue,
+            };
+            if vars.insert(var, is_seq) == Some(!is_seq) {
+                return Err(format!(
+                    "Variable {} stands for both a quote and a sequence of terms",
+                    engine.resolve(&var)
+                ));
+            }
+        }
+        Ok(())
+    }
+    let mut vars = HashMap::new();
+    go(engine, redex, &mut vars)?;
+    Ok(vars)
+}
+
+/// Turns the words of a reduction that name variables of its redex into those variables
+fn bind_variables<I: Interner>(
+    engine: &Engine<I>,
+    vars: &HashMap<Spur, bool>,
+    reduction: Vector<Term>,
+) -> Result<Vector<Term>, String> {
+    let mut terms = Vector::new();
+    for term in reduction {
+        terms.push_back(match term {
+            Term::Word(s) if is_variable(&engine.resolve(&s)) => match vars.get(&s) {
+                Some(true) => Term::make_seq_var(engine, s),
+                Some(false) => Term::make_var(engine, s),
+                None => {
+                    return Err(format!(
+                        "Variable {} is not bound by the redex",
+                        engine.resolve(&s)
+                    ))
+                }
+            },
+            Term::Quote(inner) => {
+                Term::make_quote(engine, bind_variables(engine, vars, inner.terms().clone())?)
+            }
+            term => term,
+        });
+    }
+    Ok(terms)
+}
+
+fn parse_rules<I: Interner>(
+    engine: &Engine<I>,
+    tokens: &mut Tokens,
+) -> Result<RuleSet, ParseError> {
+    let mut rules = RuleSet::new();
     loop {
         match parse_rule(engine, tokens) {
-            Ok(rule) => {
-                rules.push_back(rule);
+            Ok((rule, span, head)) => {
+                rules.insert_located(rule, Location { span, head });
             }
             Err(ParseError::Consumed(err)) => return Err(ParseError::Consumed(err)),
             Err(ParseError::DidNotConsume(_)) => break,
@@ -299,7 +404,7 @@ fn parse_rules(engine: &Engine, tokens: &mut Tokens) -> Result<Vector<Rule>, Par
 /// # Errors
 ///
 /// Returns an `Err` if the string was not a valid term
-pub fn term<'a>(engine: &'a Engine, input: &str) -> Result<&'a mut Term, String> {
+pub fn term<I: Interner>(engine: &Engine<I>, input: &str) -> Result<Term, String> {
     let mut tokens = Tokens::new(input);
     match parse_term(engine, &mut tokens) {
         Ok(term) => {
@@ -321,7 +426,7 @@ pub fn term<'a>(engine: &'a Engine, input: &str) -> Result<&'a mut Term, String>
 /// # Errors
 ///
 /// Returns an `Err` if the string was not a valid sequence of terms
-pub fn terms(engine: &Engine, input: &str) -> Result<Vector<Term>, String> {
+pub fn terms<I: Interner>(engine: &Engine<I>, input: &str) -> Result<Vector<Term>, String> {
     let mut tokens = Tokens::new(input);
     match parse_terms(engine, &mut tokens) {
         Ok(terms) => {
@@ -340,13 +445,16 @@ pub fn terms(engine: &Engine, input: &str) -> Result<Vector<Term>, String> {
 
 /// Parses a string into a rule or a string error
 ///
+/// Words starting with `$` are variables. In a redex, `$A` stands for any quote, while inside of
+/// a quote it stands for any sequence of terms. Every other word, whatever its case, is a word.
+///
 /// # Errors
 ///
 /// Returns an `Err` if the string was not a valid rule
-pub fn rule(engine: &Engine, input: &str) -> Result<Rule, String> {
+pub fn rule<I: Interner>(engine: &Engine<I>, input: &str) -> Result<Rule, String> {
     let mut tokens = Tokens::new(input);
     match parse_rule(engine, &mut tokens) {
-        Ok(rule) => {
+        Ok((rule, _, _)) => {
             if tokens.at_eoi() {
                 Ok(rule)
             } else {
@@ -362,10 +470,16 @@ pub fn rule(engine: &Engine, input: &str) -> Result<Rule, String> {
 
 /// Parses a string into a sequence of rules or a string error
 ///
+/// Words starting with `$` are variables. In a redex, `$A` stands for any quote, while inside of
+/// a quote it stands for any sequence of terms. Every other word, whatever its case, is a word.
+///
+/// The set keeps where each rule was in the string, which `Engine::define` can record as the
+/// definitions of the words the rules define.
+///
 /// # Errors
 ///
 /// Returns an `Err` if the string was not a valid sequence of rules
-pub fn rules(engine: &Engine, input: &str) -> Result<Vector<Rule>, String> {
+pub fn rules<I: Interner>(engine: &Engine<I>, input: &str) -> Result<RuleSet, String> {
     let mut tokens = Tokens::new(input);
     match parse_rules(engine, &mut tokens) {
         Ok(rules) => {
@@ -0,0 +1,314 @@
+use crate::{Engine, Interner, Rule, Term};
+use im::{vector, HashMap, HashSet, Vector};
+use lasso::Spur;
+
+/// What each variable of a redex was bound to by a match, as the contents of a quote
+pub type Bindings = HashMap<Spur, Vector<Term>>;
+
+/// Matches a sequence of terms against a redex, binding the variables of the redex
+#[must_use]
+pub fn bind(redex: &Vector<Term>, terms: &Vector<Term>) -> Option<Bindings> {
+    bind_sequence(redex, terms, Bindings::new())
+}
+
+/// Matches a sequence of terms against a sequence of patterns, trying every split of the
+/// terms a sequence variable could stand for from the shortest up
+fn bind_sequence(
+    patterns: &Vector<Term>,
+    terms: &Vector<Term>,
+    mut bindings: Bindings,
+) -> Option<Bindings> {
+    match patterns.front() {
+        None => terms.is_empty().then_some(bindings),
+        Some(Term::SeqVar(var)) => {
+            let rest = patterns.skip(1);
+            if let Some(bound) = bindings.get(var) {
+                let length = bound.len();
+                return (length <= terms.len() && &terms.take(length) == bound)
+                    .then(|| bind_sequence(&rest, &terms.skip(length), bindings.clone()))
+                    .flatten();
+            }
+            (0..=terms.len()).find_map(|length| {
+                let bindings = bindings.update(*var, terms.take(length));
+                bind_sequence(&rest, &terms.skip(length), bindings)
+            })
+        }
+        Some(pattern) => {
+            let term = terms.front()?;
+            match (pattern, term) {
+                (Term::Var(var), Term::Quote(inner)) => bind_var(&mut bindings, *var, inner)?,
+                (Term::Quote(patterns), Term::Quote(inner)) => {
+                    bindings = bind_sequence(patterns, inner, bindings)?;
+                }
+                _ if pattern == term => {}
+                _ => return None,
+            }
+            bind_sequence(&patterns.skip(1), &terms.skip(1), bindings)
+        }
+    }
+}
+
+fn bind_var(bindings: &mut Bindings, var: Spur, terms: &Vector<Term>) -> Option<()> {
+    match bindings.get(&var) {
+        Some(bound) if bound != terms => None,
+        _ => {
+            bindings.insert(var, terms.clone());
+            Some(())
+        }
+    }
+}
+
+/// Replaces the variables in a reduction with what they were bound to
+#[must_use]
+pub fn substitute<I: Interner>(
+    engine: &Engine<I>,
+    reduction: &Vector<Term>,
+    bindings: &Bindings,
+) -> Vector<Term> {
+    if bindings.is_empty() {
+        return reduction.clone();
+    }
+    let mut terms = Vector::new();
+    for term in reduction {
+        match term {
+            Term::Var(var) if bindings.contains_key(var) => {
+                terms.push_back(Term::make_quote(engine, bindings[var].clone()));
+            }
+            Term::SeqVar(var) if bindings.contains_key(var) => {
+                terms.append(bindings[var].clone());
+            }
+            Term::Quote(inner) => {
+                let inner = substitute(engine, inner, bindings);
+                terms.push_back(Term::make_quote(engine, inner));
+            }
+            _ => terms.push_back(term.clone()),
+        }
+    }
+    terms
+}
+
+/// Finds every variable in a sequence of terms
+#[must_use]
+pub fn variables(terms: &Vector<Term>) -> HashSet<Spur> {
+    let mut vars = HashSet::new();
+    for term in terms {
+        match term {
+            Term::Var(var) | Term::SeqVar(var) => {
+                vars.insert(*var);
+            }
+            Term::Quote(inner) => vars.extend(variables(inner)),
+            Term::Word(_) | Term::Prim(_) => {}
+        }
+    }
+    vars
+}
+
+/// Counts how often a variable occurs in a sequence of terms, including inside of quotes
+#[must_use]
+pub fn occurrences(terms: &Vector<Term>, var: Spur) -> usize {
+    terms
+        .iter()
+        .map(|term| match term {
+            Term::Var(v) | Term::SeqVar(v) if *v == var => 1,
+            Term::Quote(inner) => occurrences(inner, var),
+            _ => 0,
+        })
+        .sum()
+}
+
+/// Replaces every variable that stands for a quote with a quote of the sequence variable of the same name,
+/// so that sequence variables are the only kind left
+#[must_use]
+pub fn desugar<I: Interner>(engine: &Engine<I>, terms: &Vector<Term>) -> Vector<Term> {
+    terms
+        .iter()
+        .map(|term| match term {
+            Term::Var(var) => {
+                let inner = vector![Term::make_seq_var(engine, *var)];
+                Term::make_quote(engine, inner)
+            }
+            Term::Quote(inner) => Term::make_quote(engine, desugar(engine, inner)),
+            _ => term.clone(),
+        })
+        .collect()
+}
+
+/// Replaces every variable with a word of the same name. Rules can not mention such words, since
+/// they would be read as variables, so the result stands for any instance of the terms.
+#[must_use]
+pub fn skolemize<I: Interner>(engine: &Engine<I>, terms: &Vector<Term>) -> Vector<Term> {
+    let mut skolemized = Vector::new();
+    for term in terms {
+        match term {
+            Term::Var(var) => {
+                let inner = vector![Term::make_word(engine, *var)];
+                skolemized.push_back(Term::make_quote(engine, inner));
+            }
+            Term::SeqVar(var) => skolemized.push_back(Term::make_word(engine, *var)),
+            Term::Quote(inner) => {
+                let inner = skolemize(engine, inner);
+                skolemized.push_back(Term::make_quote(engine, inner));
+            }
+            _ => skolemized.push_back(term.clone()),
+        }
+    }
+    skolemized
+}
+
+/// Gives the variables of a rule new names that do not clash with the names of the variables to
+/// avoid, as generated words
+#[must_use]
+pub fn rename<I: Interner>(engine: &Engine<I>, rule: &Rule, avoid: &HashSet<Spur>) -> Rule {
+    let name = |var: &Spur| engine.resolve(var).to_string();
+    let vars = variables(&rule.redex);
+    let clashing: HashSet<String> = avoid.iter().map(name).collect();
+    let mut taken = clashing.clone().union(vars.iter().map(name).collect());
+    let mut renamed = HashMap::new();
+    for var in vars {
+        let mut fresh = name(&var);
+        if clashing.contains(&fresh) {
+            while taken.contains(&fresh) {
+                fresh.push('\'');
+            }
+            taken.insert(fresh.clone());
+            renamed.insert(var, engine.generate(&fresh));
+        } else {
+            renamed.insert(var, var);
+        }
+    }
+    let rename_all = |terms: &Vector<Term>| rename_terms(engine, terms, &renamed);
+    Rule {
+        redex: rename_all(&rule.redex),
+        reduction: rename_all(&rule.reduction),
+    }
+}
+
+fn rename_terms<I: Interner>(
+    engine: &Engine<I>,
+    terms: &Vector<Term>,
+    renamed: &HashMap<Spur, Spur>,
+) -> Vector<Term> {
+    terms
+        .iter()
+        .map(|term| match term {
+            Term::Var(var) => Term::make_var(engine, renamed[var]),
+            Term::SeqVar(var) => Term::make_seq_var(engine, renamed[var]),
+            Term::Quote(inner) => Term::make_quote(engine, rename_terms(engine, inner, renamed)),
+            _ => term.clone(),
+        })
+        .collect()
+}
+
+/// Replaces bound variables until none are left, even inside of what other variables are bound to
+#[must_use]
+pub fn resolve<I: Interner>(
+    engine: &Engine<I>,
+    terms: &Vector<Term>,
+    bindings: &Bindings,
+) -> Vector<Term> {
+    let mut terms = terms.clone();
+    loop {
+        let substituted = substitute(engine, &terms, bindings);
+        if substituted == terms {
+            return terms;
+        }
+        terms = substituted;
+    }
+}
+
+/// Finds the ways two desugared sequences of terms can be made equal by binding their variables.
+///
+/// A sequence variable is only ever bound to whole prefixes of the other side, never to part of
+/// another sequence variable, so the unifiers found are not always complete, nor always the most
+/// general ones.
+#[must_use]
+pub fn unify<I: Interner>(
+    engine: &Engine<I>,
+    left: &Vector<Term>,
+    right: &Vector<Term>,
+) -> Vec<Bindings> {
+    unify_sequences(engine, left.clone(), right.clone(), Bindings::new())
+        .into_iter()
+        .map(|bindings| {
+            bindings
+                .keys()
+                .map(|var| (*var, resolve(engine, &bindings[var], &bindings)))
+                .collect()
+        })
+        .collect()
+}
+
+/// Splices in what a bound sequence variable at the front of the sequence stands for
+fn expand_front(mut terms: Vector<Term>, bindings: &Bindings) -> Vector<Term> {
+    while let Some(Term::SeqVar(var)) = terms.front() {
+        match bindings.get(var) {
+            Some(bound) => terms = bound.clone() + terms.skip(1),
+            None => break,
+        }
+    }
+    terms
+}
+
+fn unify_sequences<I: Interner>(
+    engine: &Engine<I>,
+    left: Vector<Term>,
+    right: Vector<Term>,
+    bindings: Bindings,
+) -> Vec<Bindings> {
+    let left = expand_front(left, &bindings);
+    let right = expand_front(right, &bindings);
+    match (left.front(), right.front()) {
+        (None, None) => vec![bindings],
+        (Some(Term::SeqVar(x)), Some(Term::SeqVar(y))) if x == y => {
+            unify_sequences(engine, left.skip(1), right.skip(1), bindings)
+        }
+        (Some(Term::SeqVar(var)), _) => split(engine, *var, &left.skip(1), &right, &bindings),
+        (_, Some(Term::SeqVar(var))) => split(engine, *var, &right.skip(1), &left, &bindings),
+        (Some(a), Some(b)) => unify_terms(engine, a, b, bindings)
+            .into_iter()
+            .flat_map(|bindings| unify_sequences(engine, left.skip(1), right.skip(1), bindings))
+            .collect(),
+        (None, Some(_)) | (Some(_), None) => vec![],
+    }
+}
+
+fn unify_terms<I: Interner>(
+    engine: &Engine<I>,
+    left: &Term,
+    right: &Term,
+    bindings: Bindings,
+) -> Vec<Bindings> {
+    match (left, right) {
+        (Term::Quote(left), Term::Quote(right)) => unify_sequences(
+            engine,
+            left.terms().clone(),
+            right.terms().clone(),
+            bindings,
+        ),
+        _ if left == right => vec![bindings],
+        _ => vec![],
+    }
+}
+
+/// Binds a sequence variable at the front of one side to the prefixes of the other side. When
+/// the other side starts with a sequence variable too, the empty prefix is left out, since
+/// binding both variables to the same sequence is more general.
+fn split<I: Interner>(
+    engine: &Engine<I>,
+    var: Spur,
+    rest: &Vector<Term>,
+    other: &Vector<Term>,
+    bindings: &Bindings,
+) -> Vec<Bindings> {
+    let shortest = usize::from(matches!(other.front(), Some(Term::SeqVar(_))));
+    (shortest..=other.len())
+        .filter_map(|length| {
+            let prefix = resolve(engine, &other.take(length), bindings);
+            (!variables(&prefix).contains(&var)).then(|| {
+                let bindings = bindings.update(var, prefix);
+                unify_sequences(engine, rest.clone(), other.skip(length), bindings)
+            })
+        })
+        .flatten()
+        .collect()
+}
@@ -1,21 +1,21 @@
-use crate::{Engine, Rule, Term};
+use crate::{Engine, Interner, Rule, RuleSet, Term};
 use im::Vector;
 
 /// Pretty prints a term into a string
 #[must_use]
-pub fn term(engine: &Engine, term: Term) -> String {
+pub fn term<I: Interner>(engine: &Engine<I>, term: Term) -> String {
     match term {
-        Term::Word(s) => engine.resolve(&s).to_string(),
+        Term::Word(s) | Term::Var(s) | Term::SeqVar(s) => engine.resolve(&s).to_string(),
         Term::Prim(primitive) => primitive.to_string(),
         Term::Quote(q) => {
-            format!("({})", terms(engine, q).trim())
+            format!("({})", terms(engine, q.terms().clone()).trim())
         }
     }
 }
 
 /// Pretty prints a sequence of terms into a string
 #[must_use]
-pub fn terms(engine: &Engine, terms: Vector<Term>) -> String {
+pub fn terms<I: Interner>(engine: &Engine<I>, terms: Vector<Term>) -> String {
     let mut s = String::new();
     for t in terms {
         s.push_str(&term(engine, t));
@@ -26,7 +26,7 @@ pub fn terms(engine: &Engine, terms: Vector<Term>) -> String {
 
 /// Pretty prints a rule into a string
 #[must_use]
-pub fn rule(engine: &Engine, rule: Rule) -> String {
+pub fn rule<I: Interner>(engine: &Engine<I>, rule: Rule) -> String {
     let Rule { redex, reduction } = rule;
     format!(
         "{} = {}.",
@@ -37,7 +37,7 @@ pub fn rule(engine: &Engine, rule: Rule) -> String {
 
 /// Pretty prints a sequence of rules into a string
 #[must_use]
-pub fn rules(engine: &Engine, rules: Vector<Rule>) -> String {
+pub fn rules<I: Interner>(engine: &Engine<I>, rules: RuleSet) -> String {
     let mut s = String::new();
     for r in rules {
         s.push_str(&rule(engine, r));
@@ -0,0 +1,283 @@
+use crate::{Location, Primitive, Rule, Term};
+use im::ordmap::ConsumingIter;
+use im::{HashMap, OrdMap, OrdSet, Vector};
+use lasso::Spur;
+
+/// What the last term of a redex has to look like, for indexing rules by it
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+enum Key {
+    Word(Spur),
+    Prim(Primitive),
+    Quote,
+}
+
+impl Key {
+    /// The key of the last term of a redex, or `None` if it can match any number of terms
+    const fn of_pattern(term: &Term) -> Option<Self> {
+        match term {
+            Term::Word(spur) => Some(Self::Word(*spur)),
+            Term::Prim(primitive) => Some(Self::Prim(*primitive)),
+            Term::Quote(_) | Term::Var(_) => Some(Self::Quote),
+            Term::SeqVar(_) => None,
+        }
+    }
+
+    /// The key of the redexes whose last term could match the given term
+    const fn of_term(term: &Term) -> Self {
+        match term {
+            Term::Word(spur) => Self::Word(*spur),
+            Term::Prim(primitive) => Self::Prim(*primitive),
+            Term::Quote(_) | Term::Var(_) | Term::SeqVar(_) => Self::Quote,
+        }
+    }
+}
+
+/// A set of rules where rules that were inserted earlier have priority, indexed for matching
+/// and for looking rules up by the word they define.
+///
+/// Every rule gets an id when it is inserted, which stays the same until it is removed. Ids count
+/// up from zero, so the ids of a set built from a sequence of rules are their indices. Rules that
+/// were parsed also keep where they were in the string they were parsed from.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct RuleSet {
+    rules: OrdMap<usize, Rule>,
+    locations: OrdMap<usize, Location>,
+    next_id: usize,
+    heads: HashMap<Spur, OrdSet<usize>>,
+    index: HashMap<(usize, Key), OrdSet<usize>>,
+    unindexed: OrdSet<usize>,
+}
+
+impl RuleSet {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Adds a rule with the lowest priority, giving back its id
+    pub fn insert(&mut self, rule: Rule) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.insert_at(id, rule);
+        id
+    }
+
+    /// Adds a rule with the lowest priority along with where it was parsed from, giving back its id
+    pub fn insert_located(&mut self, rule: Rule, location: Location) -> usize {
+        let id = self.insert(rule);
+        self.locations.insert(id, location);
+        id
+    }
+
+    fn insert_at(&mut self, id: usize, rule: Rule) {
+        if let Some(head) = rule.head() {
+            self.heads.entry(head).or_default().insert(id);
+        }
+        match rule.redex.back().and_then(Key::of_pattern) {
+            Some(key) => {
+                self.index
+                    .entry((rule.redex.len(), key))
+                    .or_default()
+                    .insert(id);
+            }
+            None => {
+                self.unindexed.insert(id);
+            }
+        }
+        self.rules.insert(id, rule);
+    }
+
+    /// Removes the rule with the given id, giving it back if there was one
+    pub fn remove(&mut self, id: usize) -> Option<Rule> {
+        let rule = self.rules.remove(&id)?;
+        self.locations.remove(&id);
+        if let Some(head) = rule.head() {
+            remove_from(&mut self.heads, &head, id);
+        }
+        match rule.redex.back().and_then(Key::of_pattern) {
+            Some(key) => remove_from(&mut self.index, &(rule.redex.len(), key), id),
+            None => {
+                self.unindexed.remove(&id);
+            }
+        }
+        Some(rule)
+    }
+
+    /// Changes every rule in the same way, keeping their ids and locations
+    #[must_use]
+    pub fn map(&self, mut f: impl FnMut(&Rule) -> Rule) -> Self {
+        let mut rules = Self {
+            locations: self.locations.clone(),
+            next_id: self.next_id,
+            ..Self::new()
+        };
+        for (id, rule) in self.iter() {
+            rules.insert_at(id, f(rule));
+        }
+        rules
+    }
+
+    /// Adds all the rules of another set with a lower priority than the rules already here,
+    /// keeping their order and giving back their new ids. Their locations are not kept, since they
+    /// are in another string than those of the rules here.
+    pub fn merge(&mut self, other: Self) -> Vec<usize> {
+        other.into_iter().map(|rule| self.insert(rule)).collect()
+    }
+
+    #[must_use]
+    pub fn get(&self, id: usize) -> Option<&Rule> {
+        self.rules.get(&id)
+    }
+
+    /// Finds where the rule with the given id was parsed from, if it was parsed
+    #[must_use]
+    pub fn location(&self, id: usize) -> Option<Location> {
+        self.locations.get(&id).copied()
+    }
+
+    /// Iterates over the rules and their ids, from the highest priority to the lowest
+    #[must_use]
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (usize, &Rule)> {
+        self.rules.iter().map(|(id, rule)| (*id, rule))
+    }
+
+    /// Iterates over the rules that define the given word, from the highest priority to the lowest
+    pub fn by_head(&self, head: Spur) -> impl Iterator<Item = (usize, &Rule)> {
+        self.heads
+            .get(&head)
+            .into_iter()
+            .flatten()
+            .map(move |id| (*id, &self.rules[id]))
+    }
+
+    /// Iterates over the rules whose redex could match the given window of terms, from the
+    /// highest priority to the lowest
+    pub fn candidates(&self, window: &Vector<Term>) -> impl Iterator<Item = (usize, &Rule)> {
+        let indexed = window
+            .back()
+            .and_then(|term| self.index.get(&(window.len(), Key::of_term(term))));
+        let mut ids: Vec<_> = indexed
+            .into_iter()
+            .flatten()
+            .chain(&self.unindexed)
+            .copied()
+            .collect();
+        if !self.unindexed.is_empty() {
+            ids.sort_unstable();
+        }
+        ids.into_iter().map(move |id| (id, &self.rules[&id]))
+    }
+}
+
+fn remove_from<K: std::hash::Hash + Eq + Clone>(
+    map: &mut HashMap<K, OrdSet<usize>>,
+    key: &K,
+    id: usize,
+) {
+    if let Some(ids) = map.get_mut(key) {
+        ids.remove(&id);
+        if ids.is_empty() {
+            map.remove(key);
+        }
+    }
+}
+
+impl FromIterator<Rule> for RuleSet {
+    fn from_iter<I: IntoIterator<Item = Rule>>(iter: I) -> Self {
+        let mut rules = Self::new();
+        for rule in iter {
+            rules.insert(rule);
+        }
+        rules
+    }
+}
+
+impl From<Vector<Rule>> for RuleSet {
+    fn from(rules: Vector<Rule>) -> Self {
+        rules.into_iter().collect()
+    }
+}
+
+impl Extend<Rule> for RuleSet {
+    fn extend<I: IntoIterator<Item = Rule>>(&mut self, iter: I) {
+        for rule in iter {
+            self.insert(rule);
+        }
+    }
+}
+
+impl IntoIterator for RuleSet {
+    type Item = Rule;
+    type IntoIter = std::iter::Map<ConsumingIter<(usize, Rule)>, fn((usize, Rule)) -> Rule>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.rules.into_iter().map(|(_, rule)| rule)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse, pretty, rewrite, Engine};
+
+    fn rewrites_to(engine: &Engine, rules: &RuleSet, begin: &str, end: &str) {
+        let rewritten = rewrite(engine, rules, parse::terms(engine, begin).unwrap());
+        assert_eq!(pretty::terms(engine, rewritten), end);
+    }
+
+    #[test]
+    fn insert_remove_test() {
+        let engine = Engine::new();
+        let mut rules = parse::rules(&engine, "x = a. x = b. (y) x = c.").unwrap();
+        assert_eq!(rules.len(), 3);
+        rewrites_to(&engine, &rules, "x", "a");
+        rewrites_to(&engine, &rules, "(y) x", "c");
+
+        assert_eq!(
+            rules.remove(0).map(|rule| pretty::rule(&engine, rule)),
+            Some("x = a.".to_owned())
+        );
+        assert_eq!(rules.remove(0), None);
+        rewrites_to(&engine, &rules, "x", "b");
+
+        let id = rules.insert(parse::rule(&engine, "x = d.").unwrap());
+        assert_eq!(id, 3);
+        rewrites_to(&engine, &rules, "x", "b");
+        rules.remove(1);
+        rules.remove(2);
+        rewrites_to(&engine, &rules, "(y) x", "(y) d");
+        assert_eq!(pretty::rules(&engine, rules), "x = d.\n");
+    }
+
+    #[test]
+    fn merge_test() {
+        let engine = Engine::new();
+        let mut rules = parse::rules(&engine, "x = a. y = b.").unwrap();
+        let other = parse::rules(&engine, "x = c. z = d.").unwrap();
+        assert_eq!(rules.merge(other), vec![2, 3]);
+        rewrites_to(&engine, &rules, "x y z", "a b d");
+        let ids: Vec<_> = rules.iter().map(|(id, _)| id).collect();
+        assert_eq!(ids, vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn by_head_test() {
+        let engine = Engine::new();
+        let rules = parse::rules(&engine, "x = a. $A y x = b. (x) y = c. x + = d.").unwrap();
+        let x = engine.get_or_intern("x".to_owned());
+        let ids: Vec<_> = rules.by_head(x).map(|(id, _)| id).collect();
+        assert_eq!(ids, vec![0, 1, 3]);
+        let z = engine.get_or_intern("z".to_owned());
+        assert_eq!(rules.by_head(z).count(), 0);
+    }
+}
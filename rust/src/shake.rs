@@ -0,0 +1,63 @@
+use crate::{graph, Rule, RuleSet, Term};
+use im::{HashSet, Vector};
+
+/// Finds the rules that can possibly fire while rewriting the entry terms, in the order of the set.
+///
+/// A rule can fire once every word in its redex can appear, and then the words in its reduction can
+/// appear too. Words can appear if they are in the entry, including inside of quotes. Rules whose
+/// redex has no words can always fire.
+#[must_use]
+pub fn rules(rules: &RuleSet, entry: &Vector<Term>) -> Vector<Rule> {
+    let mut words = HashSet::new();
+    graph::words(entry, &mut words);
+    let mut live = vec![false; rules.len()];
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for ((_, rule), live) in rules.iter().zip(&mut live) {
+            if *live {
+                continue;
+            }
+            let mut needed = HashSet::new();
+            graph::words(&rule.redex, &mut needed);
+            if needed.is_subset(&words) {
+                *live = true;
+                changed = true;
+                graph::words(&rule.reduction, &mut words);
+            }
+        }
+    }
+    rules
+        .iter()
+        .zip(live)
+        .filter(|(_, live)| *live)
+        .map(|((_, rule), _)| rule.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parse, pretty, Engine};
+
+    #[test]
+    fn rules_test() {
+        let engine = Engine::new();
+        let rules = parse::rules(
+            &engine,
+            "($A) dup = ($A) ($A). ($A) twice = ($A) dup ,. ($A) ($B) swap = ($B) ($A). \
+             main = (x) twice (swap) <. x y = z. z = w. ($A) ($B) ~ = ($A).",
+        )
+        .unwrap();
+        let shake = |input| {
+            let entry = parse::terms(&engine, input).unwrap();
+            pretty::rules(&engine, super::rules(&rules, &entry).into_iter().collect())
+        };
+        assert_eq!(
+            shake("main"),
+            "($A) dup = ($A) ($A).\n($A) twice = ($A) dup ,.\n($A) ($B) swap = ($B) ($A).\n\
+             main = (x) twice (swap) <.\n($A) ($B) ~ = ($A).\n"
+        );
+        assert_eq!(shake("(y) x"), "x y = z.\nz = w.\n($A) ($B) ~ = ($A).\n");
+        assert_eq!(shake("a"), "($A) ($B) ~ = ($A).\n");
+    }
+}
@@ -0,0 +1,209 @@
+use crate::{pattern, pretty, Engine, Interner, Primitive, RuleSet, Term};
+use im::Vector;
+use lasso::Spur;
+
+/// Why a term does not reduce
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Reason {
+    /// No rule is headed by the word
+    Undefined(Spur),
+    /// Rules are headed by the word, but none of them matches the terms around it
+    Unmatched(Spur),
+    /// The primitive takes more quotes than there are terms before it
+    Missing {
+        primitive: Primitive,
+        needed: usize,
+        found: usize,
+    },
+    /// The primitive takes a quote from where there is a term that is not a quote
+    NotQuote { primitive: Primitive, term: Term },
+}
+
+/// A term in a sequence of terms that does not reduce
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Stuck {
+    /// The indices of the quotes the term is inside of, from the outermost in
+    pub path: Vec<usize>,
+    /// The index of the term, inside of the innermost quote if there is one
+    pub position: usize,
+    pub reason: Reason,
+}
+
+impl Stuck {
+    /// Explains why the term does not reduce
+    #[must_use]
+    pub fn describe<I: Interner>(&self, engine: &Engine<I>) -> String {
+        match &self.reason {
+            Reason::Undefined(word) => {
+                format!("`{}` is not defined by any rule", engine.resolve(word))
+            }
+            Reason::Unmatched(word) => format!(
+                "no rule defining `{}` matches the terms around it",
+                engine.resolve(word)
+            ),
+            Reason::Missing {
+                primitive,
+                needed,
+                found,
+            } => format!(
+                "`{primitive}` takes {needed} {} but there {}",
+                if *needed == 1 { "quote" } else { "quotes" },
+                match found {
+                    0 => "are none before it".to_owned(),
+                    1 => "is only 1 before it".to_owned(),
+                    n => format!("are only {n} before it"),
+                }
+            ),
+            Reason::NotQuote { primitive, term } => format!(
+                "`{primitive}` takes a quote but `{}` is not a quote",
+                pretty::term(engine, term.clone())
+            ),
+        }
+    }
+}
+
+/// How many quotes a primitive takes
+const fn needed(primitive: Primitive) -> usize {
+    match primitive {
+        Primitive::Copy | Primitive::Discard | Primitive::Wrap | Primitive::Unwrap => 1,
+        Primitive::Swap | Primitive::Combine => 2,
+    }
+}
+
+/// Finds why a word or primitive does not reduce, or `None` if it does or is not either
+fn reason(rules: &RuleSet, terms: &Vector<Term>, position: usize) -> Option<Reason> {
+    match &terms[position] {
+        Term::Word(word) => {
+            if rules.by_head(*word).next().is_none() {
+                return Some(Reason::Undefined(*word));
+            }
+            let matches = rules.by_head(*word).any(|(_, rule)| {
+                rule.redex
+                    .iter()
+                    .rposition(|term| term == &Term::Word(*word))
+                    .and_then(|index| position.checked_sub(index))
+                    .filter(|start| start + rule.redex.len() <= terms.len())
+                    .and_then(|start| {
+                        let window = terms.skip(start).take(rule.redex.len());
+                        pattern::bind(&rule.redex, &window)
+                    })
+                    .is_some()
+            });
+            (!matches).then_some(Reason::Unmatched(*word))
+        }
+        Term::Prim(primitive) => {
+            let needed = needed(*primitive);
+            let before = terms.take(position);
+            let quotes = before
+                .iter()
+                .rev()
+                .take(needed)
+                .take_while(|term| matches!(term, Term::Quote(_)))
+                .count();
+            if quotes == needed {
+                None
+            } else if let Some(term) = before.iter().rev().nth(quotes) {
+                Some(Reason::NotQuote {
+                    primitive: *primitive,
+                    term: term.clone(),
+                })
+            } else {
+                Some(Reason::Missing {
+                    primitive: *primitive,
+                    needed,
+                    found: quotes,
+                })
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Finds every word and primitive that does not reduce in a sequence of terms, including inside of
+/// quotes.
+///
+/// This is meant for a normal form `rewrite` returned. Words that no rule is headed by are
+/// reported even though they may be meant as data.
+#[must_use]
+pub fn terms(rules: &RuleSet, terms: &Vector<Term>) -> Vec<Stuck> {
+    fn go(rules: &RuleSet, terms: &Vector<Term>, path: &mut Vec<usize>, found: &mut Vec<Stuck>) {
+        for (position, term) in terms.iter().enumerate() {
+            let reason = match term {
+                Term::Quote(inner) => {
+                    path.push(position);
+                    go(rules, inner, path, found);
+                    path.pop();
+                    None
+                }
+                _ => reason(rules, terms, position),
+            };
+            if let Some(reason) = reason {
+                found.push(Stuck {
+                    path: path.clone(),
+                    position,
+                    reason,
+                });
+            }
+        }
+    }
+    let mut found = Vec::new();
+    go(rules, terms, &mut Vec::new(), &mut found);
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse, rewrite};
+
+    #[test]
+    fn terms_test() {
+        let engine = Engine::new();
+        let rules = parse::rules(&engine, "($A) ($B) swap = ($B) ($A). ($A) f = $A.").unwrap();
+        let reasons = |input| {
+            let normal = rewrite(&engine, &rules, parse::terms(&engine, input).unwrap());
+            terms(&rules, &normal)
+                .iter()
+                .map(|stuck| (stuck.path.clone(), stuck.position, stuck.describe(&engine)))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            reasons("x +"),
+            vec![
+                (vec![], 0, "`x` is not defined by any rule".to_owned()),
+                (
+                    vec![],
+                    1,
+                    "`+` takes a quote but `x` is not a quote".to_owned()
+                ),
+            ]
+        );
+        assert_eq!(
+            reasons("(x) ~"),
+            vec![
+                (vec![0], 0, "`x` is not defined by any rule".to_owned()),
+                (
+                    vec![],
+                    1,
+                    "`~` takes 2 quotes but there is only 1 before it".to_owned()
+                ),
+            ]
+        );
+        assert_eq!(
+            reasons("(-) swap"),
+            vec![
+                (
+                    vec![0],
+                    0,
+                    "`-` takes 1 quote but there are none before it".to_owned()
+                ),
+                (
+                    vec![],
+                    1,
+                    "no rule defining `swap` matches the terms around it".to_owned()
+                ),
+            ]
+        );
+        assert_eq!(reasons("(()) (()) swap f"), vec![]);
+    }
+}
@@ -0,0 +1,132 @@
+/// A range of bytes in the string a part of a program was parsed from
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    #[must_use]
+    pub const fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+}
+
+/// Where a rule is in the string it was parsed from
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Location {
+    /// Where the whole rule is
+    pub span: Span,
+    /// Where the word the rule defines is in its redex, if it defines one
+    pub head: Option<Span>,
+}
+
+/// A rule that defines a word, as recorded by `Engine::define`
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Definition {
+    /// The id the caller gave the source the rule was parsed from
+    pub source: usize,
+    /// The id of the rule in the set of rules it was parsed into
+    pub rule: usize,
+    /// Where the whole rule is
+    pub span: Span,
+    /// Where the word is in the redex of the rule
+    pub head: Span,
+}
+
+/// What an engine knows about a word
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Default)]
+pub struct Symbol {
+    /// The rules that define the word, in the order they were recorded
+    pub definitions: Vec<Definition>,
+    pub doc: Option<String>,
+    /// The declared number of quotes the word takes and gives back
+    pub arity: Option<(usize, usize)>,
+    /// Whether the word is meant to be used by other programs
+    pub public: bool,
+}
+
+impl Symbol {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Iterates over the ids of the rules that define the word
+    pub fn rules(&self) -> impl Iterator<Item = usize> + '_ {
+        self.definitions.iter().map(|definition| definition.rule)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse, Engine};
+    use std::sync::Arc;
+
+    #[test]
+    fn definitions_test() {
+        let engine = Engine::new();
+        let input = "x = a.\n($A) x y = b.  (é) x = c.";
+        let mut rules = parse::rules(&engine, input).unwrap();
+        let x = engine.get("x").unwrap();
+        assert_eq!(engine.symbol(x), None);
+        engine.define(0, &rules);
+        let symbol = engine.symbol(x).unwrap();
+        assert_eq!(symbol.rules().collect::<Vec<_>>(), vec![0, 2]);
+        let spans: Vec<_> = symbol
+            .definitions
+            .iter()
+            .map(|definition| {
+                (
+                    &input[definition.span.start..definition.span.end],
+                    definition.head,
+                )
+            })
+            .collect();
+        assert_eq!(
+            spans,
+            vec![
+                ("x = a.", Span::new(0, 1)),
+                ("(é) x = c.", Span::new(27, 28))
+            ]
+        );
+        let y = engine.get("y").unwrap();
+        assert_eq!(engine.symbol(y).unwrap().definitions[0].rule, 1);
+        assert_eq!(rules.by_head(y).count(), 1);
+        assert_eq!(engine.symbol(engine.get("a").unwrap()), None);
+
+        let other = parse::rules(&engine, "y = d. x = e.").unwrap();
+        engine.define(1, &other);
+        rules.remove(0);
+        rules.insert(parse::rule(&engine, "x = f.").unwrap());
+        engine.define(0, &rules);
+        let sources = |word| {
+            let symbol = engine.symbol(word).unwrap();
+            symbol
+                .definitions
+                .iter()
+                .map(|definition| (definition.source, definition.rule))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(sources(x), vec![(1, 1), (0, 2)]);
+        assert_eq!(sources(y), vec![(1, 0), (0, 1)]);
+    }
+
+    #[test]
+    fn update_test() {
+        let parent = Arc::new(Engine::new());
+        let x = parent.get_or_intern("x".to_owned());
+        parent.update_symbol(x, |symbol| {
+            symbol.doc = Some("Does nothing".to_owned());
+            symbol.public = true;
+        });
+        let child = parent.child();
+        child.update_symbol(x, |symbol| symbol.arity = Some((1, 2)));
+        let symbol = child.symbol(x).unwrap();
+        assert_eq!(symbol.doc.as_deref(), Some("Does nothing"));
+        assert_eq!((symbol.public, symbol.arity), (true, Some((1, 2))));
+        assert_eq!(parent.symbol(x).unwrap().arity, None);
+        assert_eq!(child.symbols(), vec![(x, symbol)]);
+    }
+}
@@ -0,0 +1,365 @@
+use crate::completion::{LengthLexicographic, Order};
+use crate::{graph, pattern, steps, Engine, Interner, Primitive, RuleSet, Term};
+use im::{HashMap, HashSet, Vector};
+use lasso::Spur;
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+
+/// An order that every rule of a set was found to decrease under
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Proof {
+    /// Every rule makes the sequence of terms smaller, counting each term and quote as one
+    Size,
+    /// Every rule makes the sequence of terms lighter, where each word weighs as much as given,
+    /// or one if it is not given, and each other term and quote weighs one
+    Weights(HashMap<Spur, usize>),
+    /// Every rule makes the sequence of terms smaller under this length-lexicographic order
+    LengthLexicographic(LengthLexicographic),
+}
+
+/// Why a set of rules could not be proven to terminate
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Reason {
+    /// The words, in dependency order, define each other through their rules
+    Cycle(Vec<Spur>),
+    /// The reduction of the rule with the given id is bigger than its redex, or copies one of its
+    /// variables
+    Increasing(usize),
+    /// The reduction of the rule with the given id copies or unwraps quotes with primitives, which
+    /// can make terms bigger in a way none of the orders measure
+    Primitive(usize),
+    /// None of the orders that were tried orders every rule
+    Unordered,
+}
+
+/// A sequence of terms that rewrites to a sequence of terms that contains it again
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Witness {
+    pub terms: Vector<Term>,
+    pub reached: Vector<Term>,
+    /// The number of reductions from the terms to what they reached
+    pub steps: usize,
+}
+
+/// The result of checking a set of rules for termination
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Termination {
+    Terminating(Proof),
+    Unknown(Vec<Reason>),
+    NonTerminating(Witness),
+}
+
+/// Checks whether rewriting with a set of rules always stops.
+///
+/// First every redex is rewritten in every possible way, looking at no more than `budget`
+/// sequences of terms, to find one that comes back. Then the size of terms, weights for words
+/// and length-lexicographic orders are tried, to prove that every rule makes terms smaller. No
+/// proof is tried if a reduction copies or unwraps quotes with primitives, since those steps can
+/// make terms bigger, like `(+ <) + <` does.
+#[must_use]
+pub fn rules<I: Interner>(engine: &Engine<I>, rules: &RuleSet, budget: usize) -> Termination {
+    for (_, rule) in rules.iter() {
+        if let Some(witness) = find_loop(
+            engine,
+            rules,
+            &pattern::skolemize(engine, &rule.redex),
+            budget,
+        ) {
+            return Termination::NonTerminating(witness);
+        }
+    }
+    let unmeasured: Vec<_> = rules
+        .iter()
+        .filter(|(_, rule)| grows(&rule.reduction))
+        .map(|(id, _)| id)
+        .collect();
+    let cycles = graph::rules(rules).recursive();
+    if unmeasured.is_empty() {
+        if let Some(proof) = prove(engine, rules, cycles.is_empty()) {
+            return Termination::Terminating(proof);
+        }
+    }
+    let mut reasons: Vec<_> = cycles.into_iter().map(Reason::Cycle).collect();
+    reasons.extend(unmeasured.into_iter().map(Reason::Primitive));
+    for (id, rule) in rules.iter() {
+        let copies = pattern::variables(&rule.reduction).into_iter().any(|var| {
+            pattern::occurrences(&rule.reduction, var) > pattern::occurrences(&rule.redex, var)
+        });
+        if copies || size(&rule.reduction) > size(&rule.redex) {
+            reasons.push(Reason::Increasing(id));
+        }
+    }
+    if reasons.is_empty() {
+        reasons.push(Reason::Unordered);
+    }
+    Termination::Unknown(reasons)
+}
+
+/// Tries the orders in turn, trying weights and precedences only if no words define each other
+fn prove<I: Interner>(engine: &Engine<I>, rules: &RuleSet, acyclic: bool) -> Option<Proof> {
+    if decreasing(rules, |redex, reduction| size(redex) > size(reduction)) {
+        return Some(Proof::Size);
+    }
+    let mut orders = vec![];
+    if acyclic {
+        let precedence = precedence(rules);
+        if let Some(weights) = weights(rules, &precedence) {
+            return Some(Proof::Weights(weights));
+        }
+        orders.push(LengthLexicographic { precedence });
+    }
+    orders.push(LengthLexicographic::new());
+    orders.into_iter().find_map(|order| {
+        let ordered = decreasing(rules, |redex, reduction| {
+            order.compare(engine, redex, reduction) == Some(Ordering::Greater)
+        });
+        ordered.then_some(Proof::LengthLexicographic(order))
+    })
+}
+
+/// Whether some terms copy or unwrap quotes with primitives, including inside of quotes
+fn grows(terms: &Vector<Term>) -> bool {
+    terms.iter().any(|term| match term {
+        Term::Prim(Primitive::Copy | Primitive::Unwrap) => true,
+        Term::Quote(inner) => grows(inner),
+        _ => false,
+    })
+}
+
+fn size(terms: &Vector<Term>) -> usize {
+    weight(terms, &HashMap::new())
+}
+
+/// Looks for a sequence of terms reachable from the start that contains the start again, or that
+/// comes back to itself
+fn find_loop<I: Interner>(
+    engine: &Engine<I>,
+    rules: &RuleSet,
+    start: &Vector<Term>,
+    budget: usize,
+) -> Option<Witness> {
+    // How each sequence of terms was first reached, and in how many steps
+    let mut seen = HashMap::unit(start.clone(), (None, 0));
+    let mut queue = VecDeque::from([start.clone()]);
+    while let Some(terms) = queue.pop_front() {
+        let depth = seen[&terms].1;
+        for step in steps(engine, rules, &terms) {
+            if contains(&step.after, start) {
+                return Some(Witness {
+                    terms: start.clone(),
+                    reached: step.after,
+                    steps: depth + 1,
+                });
+            }
+            if let Some((_, before)) = seen.get(&step.after) {
+                if leads_to(&seen, &step.after, &terms) {
+                    return Some(Witness {
+                        terms: step.after.clone(),
+                        reached: step.after,
+                        steps: depth + 1 - before,
+                    });
+                }
+                continue;
+            }
+            if seen.len() >= budget {
+                return None;
+            }
+            seen.insert(step.after.clone(), (Some(terms.clone()), depth + 1));
+            queue.push_back(step.after);
+        }
+    }
+    None
+}
+
+/// Whether the first sequence of terms is on the way that the second one was first reached
+fn leads_to(
+    seen: &HashMap<Vector<Term>, (Option<Vector<Term>>, usize)>,
+    from: &Vector<Term>,
+    to: &Vector<Term>,
+) -> bool {
+    let mut terms = Some(to);
+    while let Some(current) = terms {
+        if current == from {
+            return true;
+        }
+        terms = seen[current].0.as_ref();
+    }
+    false
+}
+
+fn contains(terms: &Vector<Term>, part: &Vector<Term>) -> bool {
+    part.len() <= terms.len()
+        && (0..=terms.len() - part.len()).any(|start| &terms.skip(start).take(part.len()) == part)
+}
+
+/// Checks that every rule decreases, and that no variable occurs more often in its reduction
+/// than in its redex, so that every instance of the rule decreases too
+fn decreasing(rules: &RuleSet, decreases: impl Fn(&Vector<Term>, &Vector<Term>) -> bool) -> bool {
+    rules.iter().all(|(_, rule)| {
+        decreases(&rule.redex, &rule.reduction)
+            && pattern::variables(&rule.reduction).into_iter().all(|var| {
+                pattern::occurrences(&rule.reduction, var) <= pattern::occurrences(&rule.redex, var)
+            })
+    })
+}
+
+/// The least weight of any instance of the terms. A sequence variable weighs nothing, since it can
+/// stand for no terms at all.
+fn weight(terms: &Vector<Term>, weights: &HashMap<Spur, usize>) -> usize {
+    terms
+        .iter()
+        .map(|term| match term {
+            Term::Word(word) => weights.get(word).copied().unwrap_or(1),
+            Term::Quote(inner) => 1 + weight(inner, weights),
+            Term::SeqVar(_) => 0,
+            _ => 1,
+        })
+        .sum()
+}
+
+/// Orders the words so that every word comes after the words it is defined in terms of
+fn precedence(rules: &RuleSet) -> Vec<Spur> {
+    fn visit(word: Spur, dependencies: &HashMap<Spur, HashSet<Spur>>, order: &mut Vec<Spur>) {
+        if order.contains(&word) {
+            return;
+        }
+        order.push(word);
+        let mut next: Vec<_> = dependencies
+            .get(&word)
+            .into_iter()
+            .flatten()
+            .copied()
+            .collect();
+        next.sort();
+        for dependency in next {
+            visit(dependency, dependencies, order);
+        }
+        // Move the word behind everything it depends on
+        order.retain(|w| *w != word);
+        order.push(word);
+    }
+    let dependencies = graph::rules(rules).dependencies;
+    let mut words: Vec<_> = dependencies.keys().copied().collect();
+    words.sort();
+    let mut order = Vec::new();
+    for word in words {
+        visit(word, &dependencies, &mut order);
+    }
+    order
+}
+
+/// Gives each defined word, in precedence order, just enough weight for its rules to decrease
+fn weights(rules: &RuleSet, precedence: &[Spur]) -> Option<HashMap<Spur, usize>> {
+    let mut weights = HashMap::new();
+    for word in precedence {
+        let mut needed = 1;
+        for (_, rule) in rules.by_head(*word) {
+            let mut without = weights.clone();
+            without.insert(*word, 0);
+            let reduction = weight(&rule.reduction, &weights);
+            let rest = weight(&rule.redex, &without);
+            needed = needed.max((reduction + 1).saturating_sub(rest));
+        }
+        weights.insert(*word, needed);
+    }
+    let decreases = decreasing(rules, |redex, reduction| {
+        weight(redex, &weights) > weight(reduction, &weights)
+    });
+    decreases.then_some(weights)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse, pretty};
+
+    fn check(engine: &Engine, input: &str) -> Termination {
+        rules(engine, &parse::rules(engine, input).unwrap(), 100)
+    }
+
+    #[test]
+    fn non_terminating_test() {
+        let engine = Engine::new();
+        match check(&engine, "x = x y.") {
+            Termination::NonTerminating(witness) => {
+                assert_eq!(pretty::terms(&engine, witness.terms), "x");
+                assert_eq!(pretty::terms(&engine, witness.reached), "x y");
+                assert_eq!(witness.steps, 1);
+            }
+            termination => panic!("expected a loop, got {termination:?}"),
+        }
+        match check(&engine, "a = b. b = a.") {
+            Termination::NonTerminating(witness) => assert_eq!(witness.steps, 2),
+            termination => panic!("expected a loop, got {termination:?}"),
+        }
+        assert!(matches!(
+            check(&engine, "x = (x) <."),
+            Termination::NonTerminating(_)
+        ));
+        assert!(matches!(
+            check(&engine, "($A) dup = ($A) ($A) dup."),
+            Termination::NonTerminating(_)
+        ));
+        // Only the primitives loop here, coming back to `(+ <) + <`
+        match check(&engine, "x = (+ <) + <.") {
+            Termination::NonTerminating(witness) => {
+                assert_eq!(pretty::terms(&engine, witness.terms), "(+ <) + <");
+                assert_eq!(witness.steps, 2);
+            }
+            termination => panic!("expected a loop, got {termination:?}"),
+        }
+    }
+
+    #[test]
+    fn terminating_test() {
+        let engine = Engine::new();
+        assert_eq!(
+            check(&engine, "x y = z. a b c = d."),
+            Termination::Terminating(Proof::Size)
+        );
+        assert_eq!(
+            check(&engine, "($A) twice = ($A) ($A)."),
+            Termination::Unknown(vec![Reason::Increasing(0)])
+        );
+        match check(&engine, "x = y y. y = z z.") {
+            Termination::Terminating(Proof::Weights(weights)) => {
+                let weight = |word: &str| weights[&engine.get_or_intern(word.to_owned())];
+                assert_eq!((weight("x"), weight("y")), (7, 3));
+            }
+            termination => panic!("expected weights, got {termination:?}"),
+        }
+        // A sequence variable can stand for no terms, so this rule does not make `() g` smaller
+        let rules = parse::rules(&engine, "($A) g = () g.").unwrap();
+        assert_eq!(prove(&engine, &rules, true), None);
+        assert!(matches!(
+            super::rules(&engine, &rules, 100),
+            Termination::NonTerminating(_)
+        ));
+        // `m <` loops, so no order may prove this
+        assert_eq!(
+            check(&engine, "m = (+ <) +."),
+            Termination::Unknown(vec![Reason::Primitive(0), Reason::Increasing(0)])
+        );
+        assert!(matches!(
+            check(&engine, "b a = a b."),
+            Termination::Terminating(Proof::LengthLexicographic(_))
+        ));
+    }
+
+    #[test]
+    fn unknown_test() {
+        let engine = Engine::new();
+        let x = engine.get_or_intern("x".to_owned());
+        let y = engine.get_or_intern("y".to_owned());
+        match check(&engine, "x = (y) foo. ($A) foo = . y = (x) foo.") {
+            Termination::Unknown(reasons) => {
+                assert_eq!(reasons.len(), 3);
+                assert!(
+                    matches!(&reasons[0], Reason::Cycle(words) if words.len() == 2 && words.contains(&x) && words.contains(&y))
+                );
+                assert_eq!(reasons[1..], [Reason::Increasing(0), Reason::Increasing(2)]);
+            }
+            termination => panic!("expected an unknown result, got {termination:?}"),
+        }
+    }
+}
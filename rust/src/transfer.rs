@@ -0,0 +1,209 @@
+use crate::{is_generated, parse, Engine, Interner, Rule, RuleSet, Term};
+use im::{HashMap, Vector};
+use lasso::{RodeoReader, Spur};
+
+/// Re-interns the words of terms, and generates the generated words again, remembering the spurs it already looked up
+struct Transfer<'s, 't, S, T> {
+    source: &'s Engine<S>,
+    target: &'t Engine<T>,
+    spurs: HashMap<Spur, Spur>,
+}
+
+impl<S: Interner, T: Interner> Transfer<'_, '_, S, T> {
+    fn spur(&mut self, spur: Spur) -> Spur {
+        if let Some(spur) = self.spurs.get(&spur) {
+            return *spur;
+        }
+        let name = self.source.resolve(&spur);
+        let transferred = if is_generated(spur) {
+            self.target.generate(&name)
+        } else {
+            self.target.get_or_intern(name.to_string())
+        };
+        self.spurs.insert(spur, transferred);
+        transferred
+    }
+
+    fn terms(&mut self, terms: &Vector<Term>) -> Vector<Term> {
+        terms
+            .iter()
+            .map(|term| match term {
+                Term::Word(spur) => Term::make_word(self.target, self.spur(*spur)),
+                Term::Var(spur) => Term::make_var(self.target, self.spur(*spur)),
+                Term::SeqVar(spur) => Term::make_seq_var(self.target, self.spur(*spur)),
+                Term::Prim(primitive) => Term::make_prim(self.target, *primitive),
+                Term::Quote(inner) => {
+                    let inner = self.terms(inner);
+                    Term::make_quote(self.target, inner)
+                }
+            })
+            .collect()
+    }
+
+    fn rule(&mut self, rule: &Rule) -> Rule {
+        Rule {
+            redex: self.terms(&rule.redex),
+            reduction: self.terms(&rule.reduction),
+        }
+    }
+}
+
+/// Copies a sequence of terms made by the source engine into the target engine
+///
+/// # Panics
+///
+/// Panics if the target engine can not intern one of the words
+#[must_use]
+pub fn terms<S: Interner, T: Interner>(
+    source: &Engine<S>,
+    target: &Engine<T>,
+    terms: &Vector<Term>,
+) -> Vector<Term> {
+    let mut transfer = Transfer {
+        source,
+        target,
+        spurs: HashMap::new(),
+    };
+    transfer.terms(terms)
+}
+
+/// Copies a rule made by the source engine into the target engine
+///
+/// # Panics
+///
+/// Panics if the target engine can not intern one of the words
+#[must_use]
+pub fn rule<S: Interner, T: Interner>(source: &Engine<S>, target: &Engine<T>, rule: &Rule) -> Rule {
+    let mut transfer = Transfer {
+        source,
+        target,
+        spurs: HashMap::new(),
+    };
+    transfer.rule(rule)
+}
+
+/// Copies a set of rules made by the source engine into the target engine, keeping their ids
+///
+/// # Panics
+///
+/// Panics if the target engine can not intern one of the words
+#[must_use]
+pub fn rules<S: Interner, T: Interner>(
+    source: &Engine<S>,
+    target: &Engine<T>,
+    rules: &RuleSet,
+) -> RuleSet {
+    let mut transfer = Transfer {
+        source,
+        target,
+        spurs: HashMap::new(),
+    };
+    rules.map(|rule| transfer.rule(rule))
+}
+
+/// A set of rules that is parsed once into a frozen engine of its own, and then copied into the
+/// engine of every program that uses it
+pub struct Prelude {
+    engine: Engine<RodeoReader>,
+    rules: RuleSet,
+}
+
+impl Prelude {
+    /// Parses the rules of a prelude
+    ///
+    /// # Errors
+    ///
+    /// Returns an `Err` if the string was not a valid sequence of rules
+    pub fn new(input: &str) -> Result<Self, String> {
+        let engine = Engine::new();
+        let rules = parse::rules(&engine, input)?;
+        Ok(Self {
+            engine: engine.freeze(),
+            rules,
+        })
+    }
+
+    #[must_use]
+    pub const fn engine(&self) -> &Engine<RodeoReader> {
+        &self.engine
+    }
+
+    #[must_use]
+    pub const fn rules(&self) -> &RuleSet {
+        &self.rules
+    }
+
+    /// Copies the rules of the prelude into another engine
+    ///
+    /// # Panics
+    ///
+    /// Panics if the engine can not intern one of the words
+    #[must_use]
+    pub fn load<I: Interner>(&self, engine: &Engine<I>) -> RuleSet {
+        rules(&self.engine, engine, &self.rules)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{pretty, rewrite};
+    use lasso::Rodeo;
+    use std::cell::RefCell;
+
+    #[test]
+    fn terms_test() {
+        let source = Engine::new();
+        let target = Engine::<RefCell<Rodeo>>::default();
+        target.get_or_intern("unrelated".to_owned());
+        let input = "a (b (a +)) ~";
+        let transferred = terms(&source, &target, &parse::terms(&source, input).unwrap());
+        assert_eq!(pretty::terms(&target, transferred.clone()), input);
+        assert_eq!(transferred, parse::terms(&target, input).unwrap());
+
+        let mut set = parse::rules(&source, "x = y. ($A) ($B) z = ($B $A).").unwrap();
+        set.remove(0);
+        let transferred = rules(&source, &target, &set);
+        assert_eq!(
+            transferred.iter().map(|(id, _)| id).collect::<Vec<_>>(),
+            vec![1]
+        );
+        assert_eq!(
+            pretty::rules(&target, transferred),
+            "($A) ($B) z = ($B $A).\n"
+        );
+    }
+
+    #[test]
+    fn generated_test() {
+        let source = Engine::new();
+        let fresh = Term::make_word(&source, source.fresh_word("x"));
+        let target = Engine::new().freeze();
+        let transferred = terms(&source, &target, &im::vector![fresh]);
+        assert_eq!(pretty::terms(&target, transferred.clone()), "x.0");
+        assert_eq!(target.get("x.0"), None);
+        let Term::Word(spur) = transferred[0] else {
+            panic!("expected a word");
+        };
+        assert_eq!(spur, target.generate("x.0"));
+    }
+
+    #[test]
+    fn prelude_test() {
+        let prelude = Prelude::new("($A) dup = ($A) ($A). ($A) ($B) swap = ($B) ($A).").unwrap();
+        for (input, output) in [("(x) dup", "(x) (x)"), ("(x) (y) swap", "(y) (x)")] {
+            let engine = Engine::new();
+            let mut rules = prelude.load(&engine);
+            rules.extend(parse::rules(&engine, "($A) twice = ($A) dup ,.").unwrap());
+            let terms = rewrite(&engine, &rules, parse::terms(&engine, input).unwrap());
+            assert_eq!(pretty::terms(&engine, terms), output);
+            let terms = parse::terms(&engine, "(z) twice").unwrap();
+            assert_eq!(
+                pretty::terms(&engine, rewrite(&engine, &rules, terms)),
+                "(z z)"
+            );
+        }
+        assert_eq!(prelude.engine().get("x"), None);
+        assert_eq!(prelude.rules().len(), 2);
+    }
+}
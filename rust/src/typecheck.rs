@@ -0,0 +1,699 @@
+use crate::{graph, Engine, Interner, Primitive, Rule, RuleSet, Span, Term};
+use im::{HashMap, HashSet, Vector};
+use lasso::Spur;
+use std::fmt;
+
+/// The type of a quote on the stack
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Type {
+    /// Any quote, standing for the same type wherever the variable occurs
+    Var(usize),
+    /// A quote that changes the stack in the given way when it is unwrapped
+    Quote(Box<Function>),
+}
+
+/// The type of a stack: any stack, the rest, with quotes of the given types on top of it, from the
+/// bottom up
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Stack {
+    pub rest: usize,
+    pub values: Vec<Type>,
+}
+
+/// The type of a sequence of terms, which takes a stack of one type to a stack of another
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Function {
+    pub input: Stack,
+    pub output: Stack,
+}
+
+impl Stack {
+    const fn rest(rest: usize) -> Self {
+        Self {
+            rest,
+            values: Vec::new(),
+        }
+    }
+}
+
+/// Gives type and stack variables readable names, in the order they are first printed
+#[derive(Default)]
+struct Names {
+    given: std::collections::HashMap<usize, String>,
+    rests: usize,
+    values: usize,
+}
+
+impl Names {
+    fn name(&mut self, var: usize, rest: bool) -> String {
+        if let Some(name) = self.given.get(&var) {
+            return name.clone();
+        }
+        let (count, first) = if rest {
+            (&mut self.rests, b'A')
+        } else {
+            (&mut self.values, b'a')
+        };
+        let letter = char::from(first + u8::try_from(*count % 26).unwrap_or(0));
+        let name = match *count / 26 {
+            0 => letter.to_string(),
+            n => format!("{letter}{n}"),
+        };
+        *count += 1;
+        self.given.insert(var, name.clone());
+        name
+    }
+
+    fn ty(&mut self, ty: &Type) -> String {
+        match ty {
+            Type::Var(var) => self.name(*var, false),
+            Type::Quote(function) => format!("({})", self.function(function)),
+        }
+    }
+
+    fn stack(&mut self, stack: &Stack) -> String {
+        let mut s = self.name(stack.rest, true);
+        for value in &stack.values {
+            s.push(' ');
+            s.push_str(&self.ty(value));
+        }
+        s
+    }
+
+    fn function(&mut self, function: &Function) -> String {
+        let input = self.stack(&function.input);
+        format!("{input} -> {}", self.stack(&function.output))
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Names::default().ty(self))
+    }
+}
+
+impl fmt::Display for Stack {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Names::default().stack(self))
+    }
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Names::default().function(self))
+    }
+}
+
+/// A rule or a sequence of terms that does not type check
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct TypeError {
+    /// The id of the rule, if the error is in a rule
+    pub rule: Option<usize>,
+    /// Where the rule is, if it was parsed by `parse::rules` into the engine
+    pub span: Option<Span>,
+    pub message: String,
+}
+
+impl fmt::Display for TypeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.rule, self.span) {
+            (Some(rule), Some(span)) => write!(
+                f,
+                "rule {rule} at {}..{}: {}",
+                span.start, span.end, self.message
+            ),
+            (Some(rule), None) => write!(f, "rule {rule}: {}", self.message),
+            (None, _) => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// The types of the words of a set of rules, and the rules that do not type check
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Types {
+    pub words: HashMap<Spur, Function>,
+    pub errors: Vec<TypeError>,
+}
+
+impl Types {
+    /// Prints the type signature of every word, one per line and ordered by name
+    #[must_use]
+    pub fn signatures<I: Interner>(&self, engine: &Engine<I>) -> String {
+        let mut signatures: Vec<_> = self
+            .words
+            .iter()
+            .map(|(word, function)| format!("{} : {function}\n", engine.resolve(word)))
+            .collect();
+        signatures.sort();
+        signatures.concat()
+    }
+}
+
+/// A type of a word, where the variables that are not fixed can be different at every use
+#[derive(Clone)]
+struct Scheme {
+    function: Function,
+    generalized: HashSet<usize>,
+}
+
+/// Two types that can not be made the same
+struct Clash;
+
+/// The variables of a rule or sequence of terms
+#[derive(Default)]
+struct Variables {
+    quotes: HashMap<Spur, Type>,
+    sequences: HashMap<Spur, Function>,
+}
+
+/// Infers types, remembering what the variables were found to stand for
+struct Checker<'r> {
+    rules: &'r RuleSet,
+    next: usize,
+    values: HashMap<usize, Type>,
+    rests: HashMap<usize, Stack>,
+    schemes: HashMap<Spur, Scheme>,
+    /// The types of the words whose rules are being checked
+    checking: HashMap<Spur, Function>,
+    errors: Vec<TypeError>,
+}
+
+impl Checker<'_> {
+    const fn fresh(&mut self) -> usize {
+        self.next += 1;
+        self.next
+    }
+
+    const fn fresh_function(&mut self) -> Function {
+        Function {
+            input: Stack::rest(self.fresh()),
+            output: Stack::rest(self.fresh()),
+        }
+    }
+
+    /// Replaces the rest of a stack with what it was found to stand for, until it stands for any stack
+    fn expand(&self, stack: &Stack) -> Stack {
+        let mut stack = stack.clone();
+        while let Some(bound) = self.rests.get(&stack.rest) {
+            let mut values = bound.values.clone();
+            values.append(&mut stack.values);
+            stack = Stack {
+                rest: bound.rest,
+                values,
+            };
+        }
+        stack
+    }
+
+    fn resolve_type(&self, ty: &Type) -> Type {
+        match ty {
+            Type::Var(var) => self
+                .values
+                .get(var)
+                .map_or_else(|| ty.clone(), |bound| self.resolve_type(bound)),
+            Type::Quote(function) => Type::Quote(Box::new(self.resolve(function))),
+        }
+    }
+
+    fn resolve_stack(&self, stack: &Stack) -> Stack {
+        let stack = self.expand(stack);
+        Stack {
+            rest: stack.rest,
+            values: stack
+                .values
+                .iter()
+                .map(|ty| self.resolve_type(ty))
+                .collect(),
+        }
+    }
+
+    fn resolve(&self, function: &Function) -> Function {
+        Function {
+            input: self.resolve_stack(&function.input),
+            output: self.resolve_stack(&function.output),
+        }
+    }
+
+    fn occurs_in_type(&self, var: usize, ty: &Type) -> bool {
+        match self.resolve_type(ty) {
+            Type::Var(other) => other == var,
+            Type::Quote(function) => {
+                self.occurs_in_stack(var, &function.input)
+                    || self.occurs_in_stack(var, &function.output)
+            }
+        }
+    }
+
+    fn occurs_in_stack(&self, var: usize, stack: &Stack) -> bool {
+        let stack = self.expand(stack);
+        stack.rest == var || stack.values.iter().any(|ty| self.occurs_in_type(var, ty))
+    }
+
+    fn unify_types(&mut self, left: &Type, right: &Type) -> Result<(), Clash> {
+        match (self.resolve_type(left), self.resolve_type(right)) {
+            (Type::Var(a), Type::Var(b)) if a == b => Ok(()),
+            (Type::Var(var), ty) | (ty, Type::Var(var)) => {
+                if self.occurs_in_type(var, &ty) {
+                    return Err(Clash);
+                }
+                self.values.insert(var, ty);
+                Ok(())
+            }
+            (Type::Quote(left), Type::Quote(right)) => {
+                self.unify_stacks(&left.input, &right.input)?;
+                self.unify_stacks(&left.output, &right.output)
+            }
+        }
+    }
+
+    fn unify_stacks(&mut self, left: &Stack, right: &Stack) -> Result<(), Clash> {
+        let (mut left, mut right) = (self.expand(left), self.expand(right));
+        loop {
+            match (left.values.pop(), right.values.pop()) {
+                (Some(a), Some(b)) => {
+                    self.unify_types(&a, &b)?;
+                    left = self.expand(&left);
+                    right = self.expand(&right);
+                }
+                (a, b) => {
+                    left.values.extend(a);
+                    right.values.extend(b);
+                    break;
+                }
+            }
+        }
+        match (left.values.is_empty(), right.values.is_empty()) {
+            (true, true) if left.rest == right.rest => Ok(()),
+            (true, _) => self.bind_rest(left.rest, right),
+            (false, _) => self.bind_rest(right.rest, left),
+        }
+    }
+
+    fn bind_rest(&mut self, rest: usize, stack: Stack) -> Result<(), Clash> {
+        if self.occurs_in_stack(rest, &stack) {
+            return Err(Clash);
+        }
+        self.rests.insert(rest, stack);
+        Ok(())
+    }
+
+    /// Takes the top quote off a stack, growing the rest of the stack if there is none
+    fn pop(&mut self, stack: &mut Stack) -> Type {
+        *stack = self.expand(stack);
+        if let Some(ty) = stack.values.pop() {
+            return ty;
+        }
+        let (rest, var) = (self.fresh(), self.fresh());
+        self.rests.insert(
+            stack.rest,
+            Stack {
+                rest,
+                values: vec![Type::Var(var)],
+            },
+        );
+        stack.rest = rest;
+        Type::Var(var)
+    }
+
+    /// Applies a function to a stack, which has to fit its input
+    fn apply(&mut self, stack: &mut Stack, function: &Function) -> Result<(), String> {
+        self.unify_stacks(stack, &function.input).map_err(|Clash| {
+            let mut names = Names::default();
+            format!(
+                "Expected a stack of type {} but found {}",
+                names.stack(&self.resolve_stack(&function.input)),
+                names.stack(&self.resolve_stack(stack))
+            )
+        })?;
+        *stack = function.output.clone();
+        Ok(())
+    }
+
+    /// Makes sure a quote has a function type, giving back that type
+    fn function(&mut self, quote: &Type) -> Result<Function, String> {
+        if let Type::Quote(function) = self.resolve_type(quote) {
+            return Ok(*function);
+        }
+        let function = self.fresh_function();
+        self.unify_types(quote, &Type::Quote(Box::new(function.clone())))
+            .map_err(|Clash| "A quote would have to contain itself".to_owned())?;
+        Ok(function)
+    }
+
+    fn infer(
+        &mut self,
+        stack: &mut Stack,
+        terms: &Vector<Term>,
+        vars: &mut Variables,
+    ) -> Result<(), String> {
+        for term in terms {
+            match term {
+                Term::Quote(inner) => {
+                    let input = Stack::rest(self.fresh());
+                    let mut output = input.clone();
+                    self.infer(&mut output, inner, vars)?;
+                    let function = Function { input, output };
+                    stack.values.push(Type::Quote(Box::new(function)));
+                }
+                Term::Var(var) => {
+                    let fresh = Type::Var(self.fresh());
+                    stack
+                        .values
+                        .push(vars.quotes.entry(*var).or_insert(fresh).clone());
+                }
+                Term::SeqVar(var) => {
+                    let fresh = self.fresh_function();
+                    let function = vars.sequences.entry(*var).or_insert(fresh).clone();
+                    self.apply(stack, &function)?;
+                }
+                Term::Word(word) => {
+                    let function = self.word(*word);
+                    self.apply(stack, &function)?;
+                }
+                Term::Prim(primitive) => self.primitive(stack, *primitive)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn primitive(&mut self, stack: &mut Stack, primitive: Primitive) -> Result<(), String> {
+        match primitive {
+            Primitive::Copy => {
+                let a = self.pop(stack);
+                stack.values.extend([a.clone(), a]);
+            }
+            Primitive::Discard => {
+                self.pop(stack);
+            }
+            Primitive::Wrap => {
+                let a = self.pop(stack);
+                let input = Stack::rest(self.fresh());
+                let mut output = input.clone();
+                output.values.push(a);
+                stack
+                    .values
+                    .push(Type::Quote(Box::new(Function { input, output })));
+            }
+            Primitive::Unwrap => {
+                let a = self.pop(stack);
+                let function = self.function(&a)?;
+                self.apply(stack, &function)?;
+            }
+            Primitive::Swap => {
+                let b = self.pop(stack);
+                let a = self.pop(stack);
+                stack.values.extend([b, a]);
+            }
+            Primitive::Combine => {
+                let b = self.pop(stack);
+                let a = self.pop(stack);
+                let (first, second) = (self.function(&a)?, self.function(&b)?);
+                let mut middle = first.output.clone();
+                self.apply(&mut middle, &second)?;
+                let function = Function {
+                    input: first.input,
+                    output: middle,
+                };
+                stack.values.push(Type::Quote(Box::new(function)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Gives back a type for a use of a word. A word that no rule defines never gives back, so
+    /// it can have any type.
+    fn word(&mut self, word: Spur) -> Function {
+        if let Some(function) = self.checking.get(&word) {
+            return function.clone();
+        }
+        if !self.schemes.contains_key(&word) {
+            if self.rules.by_head(word).next().is_none() {
+                return self.fresh_function();
+            }
+            self.check_word(word);
+        }
+        let scheme = self.schemes[&word].clone();
+        self.instantiate(&scheme)
+    }
+
+    fn instantiate(&mut self, scheme: &Scheme) -> Function {
+        fn rename_type(ty: &Type, renamed: &HashMap<usize, usize>) -> Type {
+            match ty {
+                Type::Var(var) => Type::Var(renamed.get(var).copied().unwrap_or(*var)),
+                Type::Quote(function) => Type::Quote(Box::new(rename(function, renamed))),
+            }
+        }
+        fn rename_stack(stack: &Stack, renamed: &HashMap<usize, usize>) -> Stack {
+            Stack {
+                rest: renamed.get(&stack.rest).copied().unwrap_or(stack.rest),
+                values: stack
+                    .values
+                    .iter()
+                    .map(|ty| rename_type(ty, renamed))
+                    .collect(),
+            }
+        }
+        fn rename(function: &Function, renamed: &HashMap<usize, usize>) -> Function {
+            Function {
+                input: rename_stack(&function.input, renamed),
+                output: rename_stack(&function.output, renamed),
+            }
+        }
+        let renamed = scheme
+            .generalized
+            .iter()
+            .map(|var| (*var, self.fresh()))
+            .collect();
+        rename(&self.resolve(&scheme.function), &renamed)
+    }
+
+    /// Checks the rules that define a word, and generalizes the type they give it over the
+    /// variables that do not occur in the types of the words that are still being checked
+    fn check_word(&mut self, word: Spur) {
+        let function = self.fresh_function();
+        self.checking.insert(word, function.clone());
+        let rules: Vec<_> = self
+            .rules
+            .by_head(word)
+            .map(|(id, rule)| (id, rule.clone()))
+            .collect();
+        for (id, rule) in rules {
+            self.check_rule(id, &rule);
+        }
+        self.checking.remove(&word);
+        let function = self.resolve(&function);
+        let mut fixed = HashSet::new();
+        for other in self.checking.values() {
+            variables(&self.resolve(other), &mut fixed);
+        }
+        let mut generalized = HashSet::new();
+        variables(&function, &mut generalized);
+        let generalized = generalized.relative_complement(fixed);
+        self.schemes.insert(
+            word,
+            Scheme {
+                function,
+                generalized,
+            },
+        );
+    }
+
+    /// Checks that the redex and the reduction of a rule have the same type
+    fn check_rule(&mut self, id: usize, rule: &Rule) {
+        let input = Stack::rest(self.fresh());
+        let mut vars = Variables::default();
+        let (mut redex, mut reduction) = (input.clone(), input);
+        let checked = self
+            .infer(&mut redex, &rule.redex, &mut vars)
+            .and_then(|()| self.infer(&mut reduction, &rule.reduction, &mut vars))
+            .and_then(|()| {
+                self.unify_stacks(&redex, &reduction).map_err(|Clash| {
+                    let mut names = Names::default();
+                    format!(
+                        "The redex gives back a stack of type {} but the reduction gives back {}",
+                        names.stack(&self.resolve_stack(&redex)),
+                        names.stack(&self.resolve_stack(&reduction))
+                    )
+                })
+            });
+        if let Err(message) = checked {
+            let span = self.rules.location(id).map(|location| location.span);
+            self.errors.push(TypeError {
+                rule: Some(id),
+                span,
+                message,
+            });
+        }
+    }
+}
+
+fn variables(function: &Function, found: &mut HashSet<usize>) {
+    fn stack(stack: &Stack, found: &mut HashSet<usize>) {
+        found.insert(stack.rest);
+        for ty in &stack.values {
+            match ty {
+                Type::Var(var) => {
+                    found.insert(*var);
+                }
+                Type::Quote(function) => variables(function, found),
+            }
+        }
+    }
+    stack(&function.input, found);
+    stack(&function.output, found);
+}
+
+/// Starts checking by checking the words the rules define, with the words each word depends on
+/// first, so that checking a word only recurses into the words of its own recursive group
+fn checker(rules: &RuleSet) -> Checker<'_> {
+    let mut checker = Checker {
+        rules,
+        next: 0,
+        values: HashMap::new(),
+        rests: HashMap::new(),
+        schemes: HashMap::new(),
+        checking: HashMap::new(),
+        errors: Vec::new(),
+    };
+    for component in graph::mentions(rules).components() {
+        for word in component {
+            let defined = rules.by_head(word).next().is_some();
+            if defined && !checker.schemes.contains_key(&word) {
+                checker.check_word(word);
+            }
+        }
+    }
+    checker
+}
+
+/// Infers the most general type of every word a set of rules defines, checking that the redex and
+/// the reduction of every rule have the same type.
+///
+/// Each word has a type that takes a stack of quotes of some types to a stack of quotes of other
+/// types, with variables that stand for any type of quote or any stack. A word that no rule
+/// defines never gives back, so it can have any type.
+#[must_use]
+pub fn rules<I: Interner>(_engine: &Engine<I>, rules: &RuleSet) -> Types {
+    let mut checker = checker(rules);
+    let mut words: Vec<_> = rules.iter().filter_map(|(_, rule)| rule.head()).collect();
+    words.sort();
+    words.dedup();
+    for (id, rule) in rules.iter() {
+        if rule.head().is_none() {
+            checker.check_rule(id, rule);
+        }
+    }
+    Types {
+        words: words
+            .into_iter()
+            .map(|word| (word, checker.resolve(&checker.schemes[&word].function)))
+            .collect(),
+        errors: checker.errors,
+    }
+}
+
+/// Infers the type of a sequence of terms, using the types of the words the rules define
+///
+/// # Errors
+///
+/// Returns an `Err` if the terms do not type check
+pub fn terms<I: Interner>(
+    _engine: &Engine<I>,
+    rules: &RuleSet,
+    terms: &Vector<Term>,
+) -> Result<Function, TypeError> {
+    let mut checker = checker(rules);
+    let input = Stack::rest(checker.fresh());
+    let mut output = input.clone();
+    checker
+        .infer(&mut output, terms, &mut Variables::default())
+        .map_err(|message| TypeError {
+            rule: None,
+            span: None,
+            message,
+        })?;
+    Ok(checker.resolve(&Function { input, output }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+    use std::fmt::Write;
+
+    fn type_of(engine: &Engine, rules: &RuleSet, input: &str) -> Result<String, TypeError> {
+        terms(engine, rules, &parse::terms(engine, input).unwrap()).map(|t| t.to_string())
+    }
+
+    #[test]
+    fn primitive_test() {
+        let engine = Engine::new();
+        let rules = RuleSet::new();
+        let ty = |input| type_of(&engine, &rules, input);
+        assert_eq!(ty("+").unwrap(), "A a -> A a a");
+        assert_eq!(ty("~").unwrap(), "A a b -> A b a");
+        assert_eq!(ty(">").unwrap(), "A a -> A (B -> B a)");
+        assert_eq!(ty("<").unwrap(), "A (A -> B) -> B");
+        assert_eq!(ty(",").unwrap(), "A (B -> C) (C -> D) -> A (B -> D)");
+        assert_eq!(ty("(-) <").unwrap(), "A a -> A");
+        assert_eq!(ty("(+) (-) , <").unwrap(), "A a -> A a");
+        assert!(ty("+ <").is_err());
+        assert!(ty("(+ <) + <").is_err());
+    }
+
+    #[test]
+    fn rules_test() {
+        let engine = Engine::new();
+        let rules = parse::rules(
+            &engine,
+            "($A) ($B) swap = ($B) ($A). ($A) dup = ($A) ($A). ($A) i = $A. twice = dup ,. \
+             ($A) ($B) pair = (($A) ($B)). x = (y) dup.",
+        )
+        .unwrap();
+        let types = super::rules(&engine, &rules);
+        assert_eq!(types.errors, vec![]);
+        assert_eq!(
+            types.signatures(&engine),
+            "dup : A (B -> C) -> A (B -> C) (B -> C)\n\
+             i : A (A -> B) -> B\n\
+             pair : A (B -> C) (D -> E) -> A (F -> F (B -> C) (D -> E))\n\
+             swap : A (B -> C) (D -> E) -> A (D -> E) (B -> C)\n\
+             twice : A (B -> B) -> A (B -> B)\n\
+             x : A -> A (B -> C) (B -> C)\n"
+        );
+        let ty = |input| type_of(&engine, &rules, input);
+        assert_eq!(ty("(+) i").unwrap(), "A a -> A a a");
+        assert_eq!(ty("(x) (-) swap i - -").unwrap(), "A -> A (B a -> B)");
+        assert!(ty("x i i").is_err());
+
+        let chain = (0..3000).fold(String::from("($A) w3000 = ."), |mut chain, n| {
+            let _ = write!(chain, " w{n} = w{}.", n + 1);
+            chain
+        });
+        let rules = parse::rules(&engine, &chain).unwrap();
+        let types = super::rules(&engine, &rules);
+        assert_eq!(types.errors, vec![]);
+        assert_eq!(
+            types.words[&engine.get("w0").unwrap()].to_string(),
+            "A (B -> C) -> A"
+        );
+    }
+
+    #[test]
+    fn error_test() {
+        let engine = Engine::new();
+        let rules = parse::rules(
+            &engine,
+            "($A) f = ($A) +. loop = loop. ($A) g = ($A) ($A) <.",
+        )
+        .unwrap();
+        let types = super::rules(&engine, &rules);
+        assert_eq!(types.errors.len(), 1);
+        let error = &types.errors[0];
+        assert_eq!(error.rule, Some(2));
+        assert_eq!(error.span, Some(Span::new(30, 51)));
+        assert!(error.to_string().starts_with("rule 2 at 30..51: "));
+        let looping = engine.get("loop").unwrap();
+        assert_eq!(types.words[&looping].to_string(), "A -> B");
+    }
+}
@@ -0,0 +1,217 @@
+use crate::{pattern, Engine, Interner, Rule, RuleSet};
+use std::fmt;
+
+/// A likely mistake in a set of rules, pointing at the rules by their id
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Issue {
+    /// The redex of the rule is empty, so it never matches
+    EmptyRedex { rule: usize },
+    /// The rule is exactly the same as an earlier rule
+    Duplicate { rule: usize, original: usize },
+    /// Every sequence of terms the redex of the rule matches is matched by an earlier rule first,
+    /// so the rule never fires
+    Shadowed { rule: usize, by: usize },
+    /// The redex of the right rule can start `offset` terms into the redex of the left rule, so
+    /// which one fires depends on the order of the rules and the strategy
+    Overlap {
+        left: usize,
+        right: usize,
+        offset: usize,
+    },
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::EmptyRedex { rule } => write!(f, "rule {rule} has an empty redex"),
+            Self::Duplicate { rule, original } => {
+                write!(f, "rule {rule} is a duplicate of rule {original}")
+            }
+            Self::Shadowed { rule, by } => {
+                write!(f, "rule {rule} is unreachable because of rule {by}")
+            }
+            Self::Overlap {
+                left,
+                right,
+                offset,
+            } => write!(
+                f,
+                "the redex of rule {right} overlaps the redex of rule {left} at offset {offset}"
+            ),
+        }
+    }
+}
+
+/// Checks a set of rules for empty redexes, duplicates, unreachable rules and overlapping redexes
+#[must_use]
+pub fn rules<I: Interner>(engine: &Engine<I>, rules: &RuleSet) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    for (index, rule) in rules.iter() {
+        if rule.redex.is_empty() {
+            issues.push(Issue::EmptyRedex { rule: index });
+            continue;
+        }
+        let instance = pattern::skolemize(engine, &rule.redex);
+        for (earlier, other) in rules.iter().take_while(|(earlier, _)| *earlier < index) {
+            if other == rule {
+                issues.push(Issue::Duplicate {
+                    rule: index,
+                    original: earlier,
+                });
+                break;
+            }
+            if pattern::bind(&other.redex, &instance).is_some() {
+                issues.push(Issue::Shadowed {
+                    rule: index,
+                    by: earlier,
+                });
+                break;
+            }
+        }
+    }
+    for (left, left_rule) in rules.iter() {
+        for (right, right_rule) in rules.iter() {
+            let hidden = issues.iter().any(|issue| match *issue {
+                Issue::EmptyRedex { rule } => rule == left || rule == right,
+                Issue::Duplicate { rule, original: by } | Issue::Shadowed { rule, by } => {
+                    (rule, by) == (left, right) || (rule, by) == (right, left)
+                }
+                Issue::Overlap { .. } => false,
+            });
+            if hidden {
+                continue;
+            }
+            for offset in overlaps(engine, left_rule, right_rule) {
+                if offset > 0 || left < right {
+                    issues.push(Issue::Overlap {
+                        left,
+                        right,
+                        offset,
+                    });
+                }
+            }
+        }
+    }
+    issues
+}
+
+/// Finds the offsets into the redex of the left rule at which the redex of the right rule can start
+fn overlaps<I: Interner>(engine: &Engine<I>, left: &Rule, right: &Rule) -> Vec<usize> {
+    let left_redex = pattern::desugar(engine, &left.redex);
+    let right = pattern::rename(engine, right, &pattern::variables(&left.redex));
+    let right_redex = pattern::desugar(engine, &right.redex);
+    (0..left_redex.len())
+        .filter(|offset| {
+            let shared = (left_redex.len() - offset).min(right_redex.len());
+            let left_part = left_redex.skip(*offset).take(shared);
+            !pattern::unify(engine, &left_part, &right_redex.take(shared)).is_empty()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    fn issues(input: &str) -> Vec<Issue> {
+        let engine = Engine::new();
+        rules(&engine, &parse::rules(&engine, input).unwrap())
+    }
+
+    #[test]
+    fn empty_redex_test() {
+        assert_eq!(issues(" = foo."), vec![Issue::EmptyRedex { rule: 0 }]);
+        assert_eq!(issues("x = y. = foo."), vec![Issue::EmptyRedex { rule: 1 }]);
+    }
+
+    #[test]
+    fn unreachable_test() {
+        assert_eq!(
+            issues("x = y. x = y."),
+            vec![Issue::Duplicate {
+                rule: 1,
+                original: 0
+            }]
+        );
+        assert_eq!(
+            issues("x = y. x = z."),
+            vec![Issue::Shadowed { rule: 1, by: 0 }]
+        );
+        assert_eq!(
+            issues("$A foo = y. (x) foo = z."),
+            vec![Issue::Shadowed { rule: 1, by: 0 }]
+        );
+        assert_eq!(
+            issues("($A) foo = $A. ($B) foo = z."),
+            vec![Issue::Shadowed { rule: 1, by: 0 }]
+        );
+        assert_eq!(
+            issues("(x) foo = z. $A foo = y."),
+            vec![Issue::Overlap {
+                left: 0,
+                right: 1,
+                offset: 0
+            }]
+        );
+    }
+
+    #[test]
+    fn overlap_test() {
+        assert_eq!(issues("x y = a. z w = b."), vec![]);
+        assert_eq!(
+            issues("x y = a. y z = b."),
+            vec![Issue::Overlap {
+                left: 0,
+                right: 1,
+                offset: 1
+            }]
+        );
+        assert_eq!(
+            issues("x y z = a. y = b."),
+            vec![Issue::Overlap {
+                left: 0,
+                right: 1,
+                offset: 1
+            }]
+        );
+        assert_eq!(
+            issues("x x = a."),
+            vec![Issue::Overlap {
+                left: 0,
+                right: 0,
+                offset: 1
+            }]
+        );
+        assert_eq!(
+            issues("$A (x $B) foo = a. foo (y) = b. ($C y) ($D) foo = c."),
+            vec![
+                Issue::Overlap {
+                    left: 0,
+                    right: 1,
+                    offset: 2
+                },
+                Issue::Overlap {
+                    left: 0,
+                    right: 2,
+                    offset: 0
+                },
+                Issue::Overlap {
+                    left: 1,
+                    right: 0,
+                    offset: 1
+                },
+                Issue::Overlap {
+                    left: 1,
+                    right: 2,
+                    offset: 1
+                },
+                Issue::Overlap {
+                    left: 2,
+                    right: 1,
+                    offset: 2
+                }
+            ]
+        );
+    }
+}